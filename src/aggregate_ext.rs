@@ -0,0 +1,158 @@
+use std::{borrow::Borrow, marker::PhantomData};
+
+use crate::{Aggregator, ModularCandle, TakerTrade};
+
+/// Turns any iterator of trades, or references to trades, into an iterator of candles,
+/// e.g.: `trades.iter().aggregate(aggregator)`.
+/// The trades are consumed lazily, only as far as needed to create the next candle,
+/// which allows aggregating streams of trades that don't fit into memory.
+pub trait AggregateExt: Iterator + Sized {
+    /// Aggregates the trades of this iterator into candles
+    ///
+    /// # Arguments:
+    /// aggregator: Something that can aggregate
+    ///
+    /// # Returns:
+    /// An iterator over the complete candles,
+    /// see `Aggregate::with_partial` to also receive the incomplete trailing candle
+    fn aggregate<A, C, T>(self, aggregator: A) -> Aggregate<Self, A, C, T>
+    where
+        Self::Item: Borrow<T>,
+        A: Aggregator<C, T>,
+        C: ModularCandle<T>,
+        T: TakerTrade,
+    {
+        Aggregate {
+            trades: self,
+            aggregator,
+            pending: Vec::new().into_iter(),
+            include_partial: false,
+            finished: false,
+            trade_type: PhantomData,
+        }
+    }
+}
+
+impl<I: Iterator> AggregateExt for I {}
+
+/// An iterator over the candles aggregated from an iterator of trades,
+/// see `AggregateExt::aggregate`
+#[derive(Debug, Clone)]
+pub struct Aggregate<I, A, C, T> {
+    trades: I,
+    aggregator: A,
+
+    // The candles created by the most recent trade, which have not been returned yet
+    pending: std::vec::IntoIter<C>,
+
+    // Whether to finish the aggregator once the trades are exhausted
+    include_partial: bool,
+
+    // Whether the trades are exhausted
+    finished: bool,
+
+    trade_type: PhantomData<T>,
+}
+
+impl<I, A, C, T> Aggregate<I, A, C, T> {
+    /// Also yields the incomplete candle at the end of the trades, see `Aggregator::finish`
+    pub fn with_partial(mut self) -> Self {
+        self.include_partial = true;
+        self
+    }
+
+    /// The underlying aggregator, e.g.: to finish the incomplete candle manually
+    pub fn into_aggregator(self) -> A {
+        self.aggregator
+    }
+}
+
+impl<I, A, C, T> Iterator for Aggregate<I, A, C, T>
+where
+    I: Iterator,
+    I::Item: Borrow<T>,
+    A: Aggregator<C, T>,
+    C: ModularCandle<T>,
+    T: TakerTrade,
+{
+    type Item = C;
+
+    fn next(&mut self) -> Option<C> {
+        loop {
+            if let Some(candle) = self.pending.next() {
+                return Some(candle);
+            }
+            if self.finished {
+                return None;
+            }
+            let mut candles = vec![];
+            match self.trades.next() {
+                Some(trade) => self.aggregator.update_into(trade.borrow(), &mut candles),
+                None => {
+                    self.finished = true;
+                    if self.include_partial {
+                        self.aggregator.finish_into(&mut candles);
+                    }
+                }
+            }
+            self.pending = candles.into_iter();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aggregate_all_trades, load_trades_from_csv, plot::OhlcCandle, By, GenericAggregator,
+        TickRule, Trade, VolumeRule,
+    };
+
+    #[test]
+    fn aggregate_ext() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+
+        let rule = VolumeRule::exact(1_000_000.0, By::Quote).unwrap();
+        let mut aggregator = GenericAggregator::<OhlcCandle, _, Trade>::new(rule.clone());
+        let expected = aggregate_all_trades(&trades, &mut aggregator);
+
+        // By reference
+        let aggregator = GenericAggregator::<OhlcCandle, _, Trade>::new(rule.clone());
+        let candles: Vec<OhlcCandle> = trades.iter().aggregate(aggregator).collect();
+        assert_eq!(candles.len(), expected.len());
+        for (c, e) in candles.iter().zip(expected.iter()) {
+            assert_eq!(c.open(), e.open());
+            assert_eq!(c.close(), e.close());
+        }
+
+        // By value, including the incomplete candle
+        let aggregator = GenericAggregator::<OhlcCandle, _, Trade>::new(rule);
+        let candles: Vec<OhlcCandle> = trades
+            .clone()
+            .into_iter()
+            .aggregate(aggregator)
+            .with_partial()
+            .collect();
+        assert_eq!(candles.len(), expected.len() + 1);
+        assert_eq!(
+            candles.last().unwrap().close(),
+            trades.last().unwrap().price
+        );
+    }
+
+    #[test]
+    fn aggregate_ext_lazy() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+
+        let mut consumed = 0;
+        let aggregator = GenericAggregator::<OhlcCandle, _, Trade>::new(TickRule::new(100));
+        let candles: Vec<OhlcCandle> = trades
+            .iter()
+            .inspect(|_| consumed += 1)
+            .aggregate(aggregator)
+            .take(2)
+            .collect();
+        assert_eq!(candles.len(), 2);
+        assert_eq!(consumed, 200);
+    }
+}
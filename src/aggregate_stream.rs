@@ -0,0 +1,204 @@
+use std::{
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_core::{ready, FusedStream, Stream};
+
+use crate::{Aggregator, ModularCandle, TakerTrade};
+
+/// An event of a live market data stream, which is aggregated by an `AggregateStream`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MarketEvent<T> {
+    /// A new trade, see `Aggregator::update`
+    Trade(T),
+
+    /// The current wall clock time, with the same resolution as the trade timestamps,
+    /// allowing time based rules to close a candle on schedule, see `Aggregator::advance_time`
+    Clock(i64),
+}
+
+impl<T: TakerTrade> From<T> for MarketEvent<T> {
+    fn from(trade: T) -> Self {
+        MarketEvent::Trade(trade)
+    }
+}
+
+/// Turns any `Stream` of trades into a `Stream` of candles, e.g.: `trades.aggregate(aggregator)`.
+/// To also close time based candles on schedule, aggregate a stream of `MarketEvent`s instead,
+/// e.g.: by merging the trades with the ticks of a wall clock using `futures::stream::select`.
+pub trait AggregateStreamExt: Stream + Sized {
+    /// Aggregates the trades of this stream into candles
+    ///
+    /// # Arguments:
+    /// aggregator: Something that can aggregate
+    ///
+    /// # Returns:
+    /// A stream of the complete candles,
+    /// see `AggregateStream::with_partial` to also receive the incomplete trailing candle
+    fn aggregate<A, C, T>(self, aggregator: A) -> AggregateStream<Self, A, C, T>
+    where
+        Self::Item: Into<MarketEvent<T>>,
+        A: Aggregator<C, T>,
+        C: ModularCandle<T>,
+        T: TakerTrade,
+    {
+        AggregateStream {
+            events: self,
+            aggregator,
+            pending: Vec::new().into_iter(),
+            include_partial: false,
+            finished: false,
+            trade_type: PhantomData,
+        }
+    }
+}
+
+impl<S: Stream> AggregateStreamExt for S {}
+
+/// A stream of the candles aggregated from a stream of trades or `MarketEvent`s,
+/// see `AggregateStreamExt::aggregate`.
+/// The input stream needs to be `Unpin`, otherwise pin it first using `Box::pin`.
+#[derive(Debug)]
+pub struct AggregateStream<S, A, C, T> {
+    events: S,
+    aggregator: A,
+
+    // The candles created by the most recent event, which have not been returned yet
+    pending: std::vec::IntoIter<C>,
+
+    // Whether to finish the aggregator once the events are exhausted
+    include_partial: bool,
+
+    // Whether the events are exhausted
+    finished: bool,
+
+    trade_type: PhantomData<T>,
+}
+
+// Only the input stream is ever polled, which is never moved once pinned itself
+impl<S: Unpin, A, C, T> Unpin for AggregateStream<S, A, C, T> {}
+
+impl<S, A, C, T> AggregateStream<S, A, C, T> {
+    /// Also yields the incomplete candle at the end of the stream, see `Aggregator::finish`
+    pub fn with_partial(mut self) -> Self {
+        self.include_partial = true;
+        self
+    }
+
+    /// The underlying aggregator, e.g.: to finish the incomplete candle manually
+    pub fn into_aggregator(self) -> A {
+        self.aggregator
+    }
+}
+
+impl<S, A, C, T> Stream for AggregateStream<S, A, C, T>
+where
+    S: Stream + Unpin,
+    S::Item: Into<MarketEvent<T>>,
+    A: Aggregator<C, T>,
+    C: ModularCandle<T>,
+    T: TakerTrade,
+{
+    type Item = C;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<C>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(candle) = this.pending.next() {
+                return Poll::Ready(Some(candle));
+            }
+            if this.finished {
+                return Poll::Ready(None);
+            }
+            let mut candles = vec![];
+            match ready!(Pin::new(&mut this.events).poll_next(cx)) {
+                Some(event) => match event.into() {
+                    MarketEvent::Trade(trade) => this.aggregator.update_into(&trade, &mut candles),
+                    MarketEvent::Clock(timestamp) => {
+                        this.aggregator.advance_time_into(timestamp, &mut candles)
+                    }
+                },
+                None => {
+                    this.finished = true;
+                    if this.include_partial {
+                        this.aggregator.finish_into(&mut candles);
+                    }
+                }
+            }
+            this.pending = candles.into_iter();
+        }
+    }
+}
+
+impl<S, A, C, T> FusedStream for AggregateStream<S, A, C, T>
+where
+    S: Stream + Unpin,
+    S::Item: Into<MarketEvent<T>>,
+    A: Aggregator<C, T>,
+    C: ModularCandle<T>,
+    T: TakerTrade,
+{
+    fn is_terminated(&self) -> bool {
+        self.finished && self.pending.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{executor::block_on, stream, StreamExt};
+
+    use super::*;
+    use crate::{
+        aggregate_all_trades, load_trades_from_csv, plot::OhlcCandle, GenericAggregator, TickRule,
+        TimeRule, TimestampResolution, Trade, M1,
+    };
+
+    #[test]
+    fn aggregate_stream() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+
+        let mut aggregator = GenericAggregator::<OhlcCandle, _, Trade>::new(TickRule::new(1000));
+        let expected = aggregate_all_trades(&trades, &mut aggregator);
+
+        let aggregator = GenericAggregator::<OhlcCandle, _, Trade>::new(TickRule::new(1000));
+        let candles: Vec<OhlcCandle> =
+            block_on(stream::iter(trades.clone()).aggregate(aggregator).collect());
+        assert_eq!(candles.len(), expected.len());
+        for (c, e) in candles.iter().zip(expected.iter()) {
+            assert_eq!(c.open(), e.open());
+            assert_eq!(c.close(), e.close());
+        }
+    }
+
+    #[test]
+    fn aggregate_stream_clock() {
+        let trade = |timestamp, price| {
+            MarketEvent::Trade(Trade {
+                timestamp,
+                price,
+                size: 1.0,
+            })
+        };
+        let events = [
+            trade(0, 100.0),
+            trade(10_000, 101.0),
+            // No trade arrives after the minute has passed, but the clock closes the candle
+            MarketEvent::Clock(60_000),
+            trade(70_000, 102.0),
+        ];
+
+        let rule = TimeRule::new(M1, TimestampResolution::Millisecond);
+        let aggregator = GenericAggregator::<OhlcCandle, _, Trade>::new(rule);
+        let mut candles = stream::iter(events).aggregate(aggregator).with_partial();
+
+        let first = block_on(candles.next()).unwrap();
+        assert_eq!(first.open(), 100.0);
+        assert_eq!(first.close(), 101.0);
+        let partial = block_on(candles.next()).unwrap();
+        assert_eq!(partial.open(), 102.0);
+        assert!(block_on(candles.next()).is_none());
+        assert!(candles.is_terminated());
+    }
+}
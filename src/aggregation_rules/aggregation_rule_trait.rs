@@ -1,5 +1,49 @@
 use crate::TakerTrade;
 
+/// The decision of an `AggregationRule` about the most recent trade
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TriggerDecision {
+    /// The aggregation needs to continue, the trade is included in the current candle
+    Continue,
+
+    /// The aggregation period finished before the trade,
+    /// so the trade is only included in the next candle
+    CloseBefore,
+
+    /// The aggregation period finished with the trade,
+    /// the `BoundaryPolicy` of the aggregator decides which candle includes it
+    CloseIncluding,
+
+    /// The aggregation period finished within the trade,
+    /// so it is split into the given size, included in the candle being closed,
+    /// and the remaining size, which is evaluated again and may therefore complete further candles.
+    /// The size has the same sign as the size of the trade.
+    /// If the trade type does not support splitting, see `TakerTrade::with_size`,
+    /// it is handled like `CloseIncluding`
+    CloseAndSplit(f64),
+}
+
+impl TriggerDecision {
+    /// Whether the aggregation period is finished and a Candle can be emitted
+    #[inline(always)]
+    pub fn is_close(&self) -> bool {
+        !matches!(self, TriggerDecision::Continue)
+    }
+}
+
+impl From<bool> for TriggerDecision {
+    /// Maps true to `CloseIncluding` and false to `Continue`
+    #[inline(always)]
+    fn from(should_trigger: bool) -> Self {
+        if should_trigger {
+            TriggerDecision::CloseIncluding
+        } else {
+            TriggerDecision::Continue
+        }
+    }
+}
+
 /// Defines under what conditions one aggregation period is finished
 /// Is generic over the type of candle being produced C,
 /// as well as the type of input trade T
@@ -8,10 +52,54 @@ pub trait AggregationRule<C, T: TakerTrade> {
     ///
     /// # Arguments:
     /// trade: The most recent taker trade (tick) information
+    /// candle: Some generic Candle, allowing for information driven decision making.
+    /// It does not include the most recent trade yet, as the decision determines where it belongs.
+    ///
+    /// # Returns:
+    /// Whether the aggregation needs to continue, or the aggregation period is finished
+    /// and a Candle can be emitted, as well as which candle includes the trade
+    fn should_trigger(&mut self, trade: &T, candle: &C) -> TriggerDecision;
+
+    /// Defines whether the aggregation is done once time has advanced without a new trade,
+    /// allowing time based rules to close a candle on schedule.
+    /// The default never triggers, as most rules only depend on the trades.
+    ///
+    /// # Arguments:
+    /// timestamp: The current time, with the same resolution as the trade timestamps
     /// candle: Some generic Candle, allowing for information driven decision making
     ///
     /// # Returns:
     /// if true, the aggregation period is finished and a Candle can be emitted
     /// else the aggregation needs to continue
-    fn should_trigger(&mut self, trade: &T, candle: &C) -> bool;
+    fn should_trigger_at(&mut self, _timestamp: i64, _candle: &C) -> bool {
+        false
+    }
+
+    /// Resets the state of the rule, such that a new aggregation period starts with the next trade.
+    /// This is used by combinators such as `AnyOf`, when another rule finished the aggregation period.
+    /// The default does nothing, which suits rules without state,
+    /// rules tracking their aggregation period need to implement it to be combined correctly.
+    fn reset(&mut self) {}
+}
+
+/// Allows using rules of differing types in the same place, e.g.: `Box<dyn AggregationRule<C, T>>`
+impl<C, T, R> AggregationRule<C, T> for Box<R>
+where
+    T: TakerTrade,
+    R: AggregationRule<C, T> + ?Sized,
+{
+    #[inline(always)]
+    fn should_trigger(&mut self, trade: &T, candle: &C) -> TriggerDecision {
+        (**self).should_trigger(trade, candle)
+    }
+
+    #[inline(always)]
+    fn should_trigger_at(&mut self, timestamp: i64, candle: &C) -> bool {
+        (**self).should_trigger_at(timestamp, candle)
+    }
+
+    #[inline(always)]
+    fn reset(&mut self) {
+        (**self).reset()
+    }
 }
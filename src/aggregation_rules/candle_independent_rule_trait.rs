@@ -0,0 +1,18 @@
+use crate::TakerTrade;
+
+/// Implemented by aggregation rules whose decisions only depend on the trades,
+/// never on the content of the candle being aggregated.
+/// This allows locating all candle boundaries before building any candle,
+/// e.g.: to build the candles in parallel, see `aggregate_all_trades_parallel`
+pub trait CandleIndependentRule {
+    /// Brings the rule into the state it has after observing `trades`,
+    /// without applying it to each of them, e.g.: counting ticks or jumping from period to period.
+    /// This allows the candles of a chunk of trades to be located without scanning the preceding chunks.
+    /// The default leaves the rule unchanged, as the state of most rules depends on every trade.
+    ///
+    /// # Returns:
+    /// Whether the rule is in the state after observing `trades`
+    fn skip_trades<T: TakerTrade>(&mut self, _trades: &[T]) -> bool {
+        false
+    }
+}
@@ -0,0 +1,711 @@
+use crate::{AggregationRule, CandleIndependentRule, ModularCandle, TakerTrade, TriggerDecision};
+
+/// Combines two rules with OR semantics,
+/// creating a candle as soon as any of the rules triggers,
+/// e.g.: every 1000 ticks or every 5 minutes, whichever comes first.
+/// Once a candle is created, the rules are brought in line with where the candle ended,
+/// so all rules start a new aggregation period in sync, see `sync_rule`.
+/// If a rule already triggers on the trade opening the next candle, that candle only holds this trade,
+/// and it is created with the following trade or time update.
+/// More than two rules can be combined by nesting, e.g.: `AnyOf::new(a, AnyOf::new(b, c))`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnyOf<A, B> {
+    a: A,
+    b: B,
+    remainder: PendingRemainder,
+
+    // Whether the previous trade completed the candle it opened
+    close_pending: bool,
+}
+
+impl<A, B> AnyOf<A, B> {
+    /// Create a new instance, combining the two given rules
+    pub fn new(a: A, b: B) -> Self {
+        Self {
+            a,
+            b,
+            remainder: PendingRemainder::default(),
+            close_pending: false,
+        }
+    }
+
+    /// Brings the rules in line with their decisions about the trade opening a candle,
+    /// closing the candle right after the trade if any of them triggers
+    fn open_candle<C, T>(&mut self, decision_a: TriggerDecision, decision_b: TriggerDecision)
+    where
+        A: AggregationRule<C, T>,
+        B: AggregationRule<C, T>,
+        C: ModularCandle<T>,
+        T: TakerTrade,
+    {
+        let close_a = closes_after_first_trade(decision_a);
+        let close_b = closes_after_first_trade(decision_b);
+        if close_a || close_b {
+            // The next period starts after the trade
+            if !close_a {
+                self.a.reset();
+            }
+            if !close_b {
+                self.b.reset();
+            }
+            self.close_pending = true;
+        }
+    }
+}
+
+impl<A, B, C, T> AggregationRule<C, T> for AnyOf<A, B>
+where
+    A: AggregationRule<C, T>,
+    B: AggregationRule<C, T>,
+    C: ModularCandle<T>,
+    T: TakerTrade,
+{
+    fn should_trigger(&mut self, trade: &T, candle: &C) -> TriggerDecision {
+        // Both rules observe the trade, so their state stays up to date,
+        // unless it is the remainder of a split trade
+        let (observe_a, observe_b) = self.remainder.observers(trade);
+        let decision_a = observe_a.then(|| self.a.should_trigger(trade, candle));
+        let decision_b = observe_b.then(|| self.b.should_trigger(trade, candle));
+
+        // The previous trade completed its candle, so this trade is the first of the next one
+        if std::mem::take(&mut self.close_pending) {
+            self.open_candle(
+                decision_a.unwrap_or(TriggerDecision::Continue),
+                decision_b.unwrap_or(TriggerDecision::Continue),
+            );
+            return TriggerDecision::CloseBefore;
+        }
+
+        let decision = earliest(
+            decision_a.unwrap_or(TriggerDecision::Continue),
+            decision_b.unwrap_or(TriggerDecision::Continue),
+        );
+        let (next_a, next_b) = sync_rules(
+            &mut self.a,
+            &mut self.b,
+            &mut self.remainder,
+            trade,
+            decision,
+            (decision_a, decision_b),
+        );
+        if decision == TriggerDecision::CloseBefore {
+            self.open_candle(next_a, next_b);
+        }
+
+        decision
+    }
+
+    fn should_trigger_at(&mut self, timestamp: i64, candle: &C) -> bool {
+        // The rules already started the period after the completed candle
+        if std::mem::take(&mut self.close_pending) {
+            return true;
+        }
+
+        let trigger_a = self.a.should_trigger_at(timestamp, candle);
+        let trigger_b = self.b.should_trigger_at(timestamp, candle);
+
+        let should_trigger = trigger_a || trigger_b;
+        if should_trigger {
+            // A rule that triggered already started its next aggregation period
+            if !trigger_a {
+                self.a.reset();
+            }
+            if !trigger_b {
+                self.b.reset();
+            }
+        }
+
+        should_trigger
+    }
+
+    fn reset(&mut self) {
+        self.a.reset();
+        self.b.reset();
+        self.remainder = PendingRemainder::default();
+        self.close_pending = false;
+    }
+}
+
+/// The remainder of a split trade, which the aggregator evaluates again right away,
+/// and which of the two rules observe it
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct PendingRemainder {
+    // The timestamp, price and size of the remainder
+    trade: Option<(i64, f64, f64)>,
+    a: bool,
+    b: bool,
+}
+
+impl PendingRemainder {
+    /// Which of the two rules observe the trade,
+    /// which is both of them, unless it is the pending remainder
+    fn observers<T: TakerTrade>(&mut self, trade: &T) -> (bool, bool) {
+        match self.trade.take() {
+            Some(remainder) if remainder == (trade.timestamp(), trade.price(), trade.size()) => {
+                (self.a, self.b)
+            }
+            _ => (true, true),
+        }
+    }
+}
+
+/// Brings a rule in line with the combined decision about a trade, given its own decision,
+/// such that every rule starts the next aggregation period with the same trade.
+///
+/// # Returns:
+/// Whether the rule observes the remainder of the trade, if it is split,
+/// and the decision of the rule about the trade as the first one of its next period,
+/// if the trade opens the next candle
+fn sync_rule<R, C, T>(
+    rule: &mut R,
+    own: TriggerDecision,
+    combined: TriggerDecision,
+    trade: &T,
+) -> (bool, TriggerDecision)
+where
+    R: AggregationRule<C, T>,
+    C: ModularCandle<T>,
+    T: TakerTrade,
+{
+    use TriggerDecision::*;
+    match (combined, own) {
+        (Continue, _) | (CloseBefore, CloseBefore) | (CloseIncluding, CloseIncluding) => {
+            (false, Continue)
+        }
+        // The trade opens the next candle, so the rule observes it again in its next period
+        (CloseBefore, _) => {
+            rule.reset();
+            (false, rule.should_trigger(trade, &C::default()))
+        }
+        // The next period starts after the trade
+        (CloseIncluding, _) | (CloseAndSplit(_), Continue) => {
+            rule.reset();
+            (false, Continue)
+        }
+        // The rule already counted the trade as a whole, so it does not observe the remainder
+        (CloseAndSplit(_), CloseBefore | CloseIncluding) => (false, Continue),
+        // The remainder of the trade is the first trade of the next period
+        (CloseAndSplit(combined_size), CloseAndSplit(size)) => {
+            if size != combined_size {
+                rule.reset();
+            }
+            (true, Continue)
+        }
+    }
+}
+
+/// Whether a decision about the first trade of a period closes the period right after that trade.
+/// The trade already opened the candle, so it can't be split anymore and closes the candle as a whole.
+fn closes_after_first_trade(decision: TriggerDecision) -> bool {
+    matches!(
+        decision,
+        TriggerDecision::CloseIncluding | TriggerDecision::CloseAndSplit(_)
+    )
+}
+
+/// Brings both rules in line with their combined decision about a trade, see `sync_rule`,
+/// remembering which of them observe the remainder of a split trade
+///
+/// # Returns:
+/// The decisions of both rules about the trade as the first one of their next period
+fn sync_rules<A, B, C, T>(
+    a: &mut A,
+    b: &mut B,
+    remainder: &mut PendingRemainder,
+    trade: &T,
+    combined: TriggerDecision,
+    decisions: (Option<TriggerDecision>, Option<TriggerDecision>),
+) -> (TriggerDecision, TriggerDecision)
+where
+    A: AggregationRule<C, T>,
+    B: AggregationRule<C, T>,
+    C: ModularCandle<T>,
+    T: TakerTrade,
+{
+    // A rule which did not observe the remainder of a split trade already started its next period
+    let (observe_a, next_a) = decisions
+        .0
+        .map_or((false, TriggerDecision::Continue), |own| {
+            sync_rule(a, own, combined, trade)
+        });
+    let (observe_b, next_b) = decisions
+        .1
+        .map_or((false, TriggerDecision::Continue), |own| {
+            sync_rule(b, own, combined, trade)
+        });
+
+    if let TriggerDecision::CloseAndSplit(size) = combined {
+        // Mirrors how the aggregator creates the remainder
+        if let Some(next) = trade.with_size(trade.size() - size) {
+            *remainder = PendingRemainder {
+                trade: Some((next.timestamp(), next.price(), next.size())),
+                a: observe_a,
+                b: observe_b,
+            };
+        }
+    }
+
+    (next_a, next_b)
+}
+
+/// The decision that ends the candle first, within or around the trade
+fn earliest(a: TriggerDecision, b: TriggerDecision) -> TriggerDecision {
+    use TriggerDecision::*;
+    match (a, b) {
+        (Continue, d) | (d, Continue) => d,
+        (CloseBefore, _) | (_, CloseBefore) => CloseBefore,
+        (CloseAndSplit(x), CloseAndSplit(y)) => {
+            CloseAndSplit(if x.abs() <= y.abs() { x } else { y })
+        }
+        (CloseAndSplit(x), CloseIncluding) | (CloseIncluding, CloseAndSplit(x)) => CloseAndSplit(x),
+        (CloseIncluding, CloseIncluding) => CloseIncluding,
+    }
+}
+
+/// The decision that ends the candle last, within or around the trade,
+/// ignoring rules that continue
+fn latest(a: TriggerDecision, b: TriggerDecision) -> TriggerDecision {
+    use TriggerDecision::*;
+    match (a, b) {
+        (Continue, d) | (d, Continue) => d,
+        (CloseIncluding, _) | (_, CloseIncluding) => CloseIncluding,
+        (CloseAndSplit(x), CloseAndSplit(y)) => {
+            CloseAndSplit(if x.abs() >= y.abs() { x } else { y })
+        }
+        (CloseAndSplit(x), CloseBefore) | (CloseBefore, CloseAndSplit(x)) => CloseAndSplit(x),
+        (CloseBefore, CloseBefore) => CloseBefore,
+    }
+}
+
+impl<A, B> CandleIndependentRule for AnyOf<A, B>
+where
+    A: CandleIndependentRule,
+    B: CandleIndependentRule,
+{
+}
+
+/// Combines two rules with AND semantics,
+/// creating a candle once all of the rules have triggered since the last candle,
+/// e.g.: at least 1000 ticks and at least 5 minutes.
+/// Once a candle is created, the rules are brought in line with where the candle ended,
+/// so all rules start a new aggregation period in sync, see `sync_rule`.
+/// More than two rules can be combined by nesting, e.g.: `AllOf::new(a, AllOf::new(b, c))`
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AllOf<A, B> {
+    a: A,
+    b: B,
+
+    // Whether the rules have triggered since the last candle
+    triggered_a: bool,
+    triggered_b: bool,
+
+    remainder: PendingRemainder,
+}
+
+impl<A, B> AllOf<A, B> {
+    /// Create a new instance, combining the two given rules
+    pub fn new(a: A, b: B) -> Self {
+        Self {
+            a,
+            b,
+            triggered_a: false,
+            triggered_b: false,
+            remainder: PendingRemainder::default(),
+        }
+    }
+}
+
+impl<A, B, C, T> AggregationRule<C, T> for AllOf<A, B>
+where
+    A: AggregationRule<C, T>,
+    B: AggregationRule<C, T>,
+    C: ModularCandle<T>,
+    T: TakerTrade,
+{
+    fn should_trigger(&mut self, trade: &T, candle: &C) -> TriggerDecision {
+        let (observe_a, observe_b) = self.remainder.observers(trade);
+        let decision_a = observe_a.then(|| self.a.should_trigger(trade, candle));
+        let decision_b = observe_b.then(|| self.b.should_trigger(trade, candle));
+
+        self.triggered_a |= decision_a.is_some_and(|d| d.is_close());
+        self.triggered_b |= decision_b.is_some_and(|d| d.is_close());
+        if !(self.triggered_a && self.triggered_b) {
+            return TriggerDecision::Continue;
+        }
+
+        // Only the rules triggering on this trade decide where the candle ends
+        let decision = latest(
+            decision_a.unwrap_or(TriggerDecision::Continue),
+            decision_b.unwrap_or(TriggerDecision::Continue),
+        );
+        let (next_a, next_b) = sync_rules(
+            &mut self.a,
+            &mut self.b,
+            &mut self.remainder,
+            trade,
+            decision,
+            (decision_a, decision_b),
+        );
+        // A rule may already trigger on the trade opening the next candle
+        self.triggered_a = closes_after_first_trade(next_a);
+        self.triggered_b = closes_after_first_trade(next_b);
+
+        decision
+    }
+
+    fn should_trigger_at(&mut self, timestamp: i64, candle: &C) -> bool {
+        let trigger_a = self.a.should_trigger_at(timestamp, candle);
+        let trigger_b = self.b.should_trigger_at(timestamp, candle);
+
+        self.triggered_a |= trigger_a;
+        self.triggered_b |= trigger_b;
+
+        let should_trigger = self.triggered_a && self.triggered_b;
+        if should_trigger {
+            // A rule that triggered on this update already started its next aggregation period
+            if !trigger_a {
+                self.a.reset();
+            }
+            if !trigger_b {
+                self.b.reset();
+            }
+            self.triggered_a = false;
+            self.triggered_b = false;
+        }
+
+        should_trigger
+    }
+
+    fn reset(&mut self) {
+        self.a.reset();
+        self.b.reset();
+        self.triggered_a = false;
+        self.triggered_b = false;
+        self.remainder = PendingRemainder::default();
+    }
+}
+
+impl<A, B> CandleIndependentRule for AllOf<A, B>
+where
+    A: CandleIndependentRule,
+    B: CandleIndependentRule,
+{
+}
+
+/// Inverts the decision of a rule,
+/// triggering on every trade where the inner rule does not trigger.
+/// Note that `AllOf` remembers that a rule has triggered until the candle is created,
+/// so `Not` is mostly useful with custom rules that express a condition on the current trade.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Not<R> {
+    rule: R,
+}
+
+impl<R> Not<R> {
+    /// Create a new instance, inverting the given rule
+    pub fn new(rule: R) -> Self {
+        Self { rule }
+    }
+}
+
+impl<R, C, T> AggregationRule<C, T> for Not<R>
+where
+    R: AggregationRule<C, T>,
+    C: ModularCandle<T>,
+    T: TakerTrade,
+{
+    fn should_trigger(&mut self, trade: &T, candle: &C) -> TriggerDecision {
+        (!self.rule.should_trigger(trade, candle).is_close()).into()
+    }
+
+    fn reset(&mut self) {
+        self.rule.reset();
+    }
+}
+
+impl<R: CandleIndependentRule> CandleIndependentRule for Not<R> {
+    fn skip_trades<T: TakerTrade>(&mut self, trades: &[T]) -> bool {
+        self.rule.skip_trades(trades)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use trade_aggregation_derive::Candle;
+
+    use super::*;
+    use crate::{
+        aggregate_all_trades,
+        candle_components::{CandleComponent, CandleComponentUpdate, NumTrades, Volume},
+        load_trades_from_csv,
+        plot::OhlcCandle,
+        Aggregator, BoundaryPolicy, By, GenericAggregator, TickRule, TimeRule, TimestampResolution,
+        Trade, VolumeRule, M1,
+    };
+
+    #[derive(Debug, Default, Clone, Candle)]
+    struct MyCandle {
+        volume: Volume,
+        num_trades: NumTrades<u32>,
+    }
+
+    fn trade(timestamp: i64, size: f64) -> Trade {
+        Trade {
+            timestamp,
+            price: 100.0,
+            size,
+        }
+    }
+
+    /// The volume and number of trades of the candles created by `rule`
+    fn candle_sizes<R>(rule: R, trades: &[Trade]) -> Vec<(f64, u32)>
+    where
+        R: AggregationRule<MyCandle, Trade>,
+    {
+        let mut aggregator = GenericAggregator::<MyCandle, R, Trade>::with_boundary_policy(
+            rule,
+            BoundaryPolicy::Closing,
+        );
+        aggregate_all_trades(trades, &mut aggregator)
+            .iter()
+            .map(|c| (c.volume(), c.num_trades()))
+            .collect()
+    }
+
+    fn triggers<R: AggregationRule<OhlcCandle, Trade>>(rule: &mut R, n: usize) -> Vec<bool> {
+        (0..n)
+            .map(|_| {
+                rule.should_trigger(&Trade::default(), &OhlcCandle::default())
+                    .is_close()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn any_of() {
+        let mut rule = AnyOf::new(TickRule::new(3), TickRule::new(5));
+
+        // The rule with 5 ticks is reset whenever the rule with 3 ticks triggers,
+        // so it never gets to trigger itself
+        let t = triggers(&mut rule, 9);
+        assert_eq!(
+            t,
+            vec![false, false, true, false, false, true, false, false, true]
+        );
+    }
+
+    #[test]
+    fn all_of() {
+        let mut rule = AllOf::new(TickRule::new(3), TickRule::new(5));
+
+        let t = triggers(&mut rule, 10);
+        assert_eq!(
+            t,
+            vec![false, false, false, false, true, false, false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn not() {
+        let mut rule = Not::new(TickRule::new(2));
+
+        let t = triggers(&mut rule, 4);
+        assert_eq!(t, vec![true, false, true, false]);
+    }
+
+    #[test]
+    fn combined_decisions() {
+        let candle = OhlcCandle::default();
+        let trade = |timestamp| Trade {
+            timestamp,
+            price: 100.0,
+            size: 1.0,
+        };
+        let time_rule = TimeRule::new(M1, TimestampResolution::Second);
+
+        // The time rule closes before the second trade, the tick rule closes including it.
+        // `AnyOf` closes the candle as early as possible, `AllOf` as late as possible
+        let mut rule = AnyOf::new(time_rule.clone(), TickRule::new(2));
+        assert_eq!(
+            rule.should_trigger(&trade(0), &candle),
+            TriggerDecision::Continue
+        );
+        assert_eq!(
+            rule.should_trigger(&trade(60), &candle),
+            TriggerDecision::CloseBefore
+        );
+
+        let mut rule = AllOf::new(time_rule, TickRule::new(2));
+        assert_eq!(
+            rule.should_trigger(&trade(0), &candle),
+            TriggerDecision::Continue
+        );
+        assert_eq!(
+            rule.should_trigger(&trade(60), &candle),
+            TriggerDecision::CloseIncluding
+        );
+
+        assert_eq!(
+            earliest(
+                TriggerDecision::CloseAndSplit(2.0),
+                TriggerDecision::CloseAndSplit(1.0)
+            ),
+            TriggerDecision::CloseAndSplit(1.0)
+        );
+        assert_eq!(
+            latest(
+                TriggerDecision::CloseBefore,
+                TriggerDecision::CloseAndSplit(1.0)
+            ),
+            TriggerDecision::CloseAndSplit(1.0)
+        );
+    }
+
+    #[test]
+    fn any_of_close_before() {
+        let rule = AnyOf::new(
+            TimeRule::new(M1, TimestampResolution::Second),
+            TickRule::new(4),
+        );
+        let trades: Vec<Trade> = [0, 10, 61, 62, 63, 64, 65, 66, 67, 68, 69]
+            .iter()
+            .map(|ts| trade(*ts, 1.0))
+            .collect();
+
+        // The trade at 61 opens the second candle, so it is the first of its 4 ticks
+        assert_eq!(
+            candle_sizes(rule, &trades),
+            vec![(2.0, 2), (4.0, 4), (4.0, 4)]
+        );
+    }
+
+    #[test]
+    fn any_of_close_before_triggers_again() {
+        let rule = AnyOf::new(
+            TimeRule::new(M1, TimestampResolution::Second),
+            VolumeRule::new(5.0, By::Quote).unwrap(),
+        );
+        let trades = [
+            trade(0, 1.0),
+            trade(10, 1.0),
+            // Opens the second candle and exceeds the volume of 5 on its own
+            trade(61, 10.0),
+            trade(62, 1.0),
+            trade(63, 1.0),
+            trade(125, 1.0),
+        ];
+
+        // The trade at 61 is the only one of the second candle
+        assert_eq!(
+            candle_sizes(rule, &trades),
+            vec![(2.0, 2), (10.0, 1), (2.0, 2)]
+        );
+
+        // Every trade exceeds the volume, so every trade is a candle of its own
+        let rule = AnyOf::new(
+            TimeRule::new(M1, TimestampResolution::Second),
+            VolumeRule::new(5.0, By::Quote).unwrap(),
+        );
+        let trades = [
+            trade(0, 1.0),
+            trade(61, 10.0),
+            trade(62, 10.0),
+            trade(63, 1.0),
+        ];
+        assert_eq!(
+            candle_sizes(rule, &trades),
+            vec![(1.0, 1), (10.0, 1), (10.0, 1)]
+        );
+    }
+
+    #[test]
+    fn all_of_close_before_triggers_again() {
+        let rule = AllOf::new(
+            TimeRule::new(M1, TimestampResolution::Second),
+            Not::new(TickRule::new(2)),
+        );
+        let mut aggregator = GenericAggregator::<MyCandle, _, Trade>::with_boundary_policy(
+            rule,
+            BoundaryPolicy::Closing,
+        );
+
+        let mut candles = vec![];
+        for t in [trade(0, 1.0), trade(61, 1.0), trade(62, 1.0)] {
+            aggregator.update_into(&t, &mut candles);
+        }
+        assert_eq!(candles.len(), 1);
+        assert_eq!(candles[0].num_trades(), 1);
+
+        // The trade at 61 is the first tick of the second candle, so the inverted tick rule triggered on it,
+        // and the time rule completes the candle
+        aggregator.advance_time_into(200, &mut candles);
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[1].num_trades(), 2);
+    }
+
+    #[test]
+    fn any_of_split() {
+        let rule = AnyOf::new(
+            VolumeRule::exact(10.0, By::Quote).unwrap(),
+            TickRule::new(3),
+        );
+        let trades = [
+            trade(0, 4.0),
+            trade(1, 3.0),
+            // completes the first candle as its third tick, two more candles and leaves 2.0
+            trade(2, 25.0),
+            trade(3, 1.0),
+            trade(4, 1.0),
+            trade(5, 1.0),
+        ];
+
+        // The tick rule counts the split trade once, in the candle it completed
+        assert_eq!(
+            candle_sizes(rule, &trades),
+            vec![(10.0, 3), (10.0, 1), (10.0, 1), (5.0, 4)]
+        );
+    }
+
+    #[test]
+    fn all_of_close_before() {
+        let rule = AllOf::new(
+            TimeRule::new(M1, TimestampResolution::Second),
+            TickRule::new(3),
+        );
+        let trades: Vec<Trade> = [0, 10, 20, 30, 61, 62, 63, 125, 126, 127]
+            .iter()
+            .map(|ts| trade(*ts, 1.0))
+            .collect();
+
+        // The trade at 61 opens the second candle, which is complete after 3 ticks,
+        // once the trade at 125 ends its period
+        assert_eq!(candle_sizes(rule, &trades), vec![(4.0, 4), (3.0, 3)]);
+    }
+
+    #[test]
+    fn any_of_real_data() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+
+        let rule = AnyOf::new(
+            TickRule::new(1000),
+            TimeRule::new(M1, TimestampResolution::Millisecond),
+        );
+        let mut aggregator = GenericAggregator::<OhlcCandle, _, Trade>::new(rule);
+        let num_any_of = aggregate_all_trades(&trades, &mut aggregator).len();
+
+        let mut aggregator = GenericAggregator::<OhlcCandle, _, Trade>::new(TimeRule::new(
+            M1,
+            TimestampResolution::Millisecond,
+        ));
+        let num_time = aggregate_all_trades(&trades, &mut aggregator).len();
+
+        // Creating a candle every 1000 ticks or 1 minute,
+        // results in at least as many candles as just the time rule
+        assert!(num_any_of >= num_time);
+        assert!(num_any_of >= 1000);
+    }
+}
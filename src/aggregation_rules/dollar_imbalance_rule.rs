@@ -0,0 +1,129 @@
+use super::imbalance_estimator::{trade_sign, ImbalanceEstimator};
+use crate::{AggregationRule, ContractType, ModularCandle, Result, TakerTrade, TriggerDecision};
+
+/// Creates candles once the signed notional value imbalance exceeds its expected value,
+/// also known as dollar imbalance bars.
+/// Each tick contributes its notional value, which is negative for sells
+/// based on the sign of `TakerTrade::size()`.
+/// A candle is finished once the absolute cumulative imbalance |θ_T| exceeds `E[T] * |E[b * v]|`,
+/// where both the expected candle length `E[T]` and the expected signed notional value per tick `E[b * v]`
+/// are estimated using exponentially weighted moving averages.
+/// See "Advances in Financial Machine Learning" by Marcos Lopez de Prado, chapter 2.3.2.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DollarImbalanceRule {
+    // See docs on ContractType enum for details
+    contract_type: ContractType,
+
+    estimator: ImbalanceEstimator,
+}
+
+impl DollarImbalanceRule {
+    /// Create a new instance of the `DollarImbalanceRule`
+    ///
+    /// # Arguments:
+    /// `contract_type`: Determines how the notional value of each trade is computed
+    /// `expected_ticks_init`: The initial expected number of ticks per candle, used during warm-up
+    /// `ticks_bounds`: The (min, max) number of ticks per candle,
+    /// which keeps the adaptive threshold from collapsing or exploding
+    /// `bar_len_span`: The span in candles of the EWMA estimating the expected candle length
+    /// `imbalance_span`: The span in ticks of the EWMA estimating the expected imbalance per tick
+    /// `warmup_bars`: The number of candles created every `expected_ticks_init` ticks,
+    /// while the expected imbalance warms up
+    ///
+    pub fn new(
+        contract_type: ContractType,
+        expected_ticks_init: usize,
+        ticks_bounds: (usize, usize),
+        bar_len_span: usize,
+        imbalance_span: usize,
+        warmup_bars: usize,
+    ) -> Result<Self> {
+        Ok(Self {
+            contract_type,
+            estimator: ImbalanceEstimator::new(
+                expected_ticks_init,
+                ticks_bounds,
+                bar_len_span,
+                imbalance_span,
+                warmup_bars,
+            )?,
+        })
+    }
+}
+
+impl<C, T> AggregationRule<C, T> for DollarImbalanceRule
+where
+    C: ModularCandle<T>,
+    T: TakerTrade,
+{
+    fn should_trigger(&mut self, trade: &T, _candle: &C) -> TriggerDecision {
+        self.estimator
+            .update(trade_sign(trade) * self.contract_type.notional(trade))
+            .into()
+    }
+
+    fn reset(&mut self) {
+        self.estimator.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aggregate_all_trades, load_trades_from_csv, plot::OhlcCandle, GenericAggregator, Trade,
+    };
+
+    fn trade(timestamp: i64, price: f64, size: f64) -> Trade {
+        Trade {
+            timestamp,
+            price,
+            size,
+        }
+    }
+
+    #[test]
+    fn dollar_imbalance_rule() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+
+        let rule =
+            DollarImbalanceRule::new(ContractType::Inverse, 1000, (100, 10_000), 10, 10_000, 10)
+                .unwrap();
+        let mut aggregator = GenericAggregator::<OhlcCandle, DollarImbalanceRule, Trade>::new(rule);
+        let candles = aggregate_all_trades(&trades, &mut aggregator);
+        // The number of ticks per candle is bounded to [100, 10_000]
+        assert!(candles.len() >= trades.len() / 10_000);
+        assert!(candles.len() <= trades.len() / 100);
+    }
+
+    #[test]
+    fn dollar_imbalance_rule_hand_built() {
+        // Both EWMAs use alpha = 0.5, the first candle is a warm-up candle of 4 ticks
+        let mut rule = DollarImbalanceRule::new(ContractType::Linear, 4, (2, 10), 3, 3, 1).unwrap();
+
+        // The signed notional values are 2, -2, -1, 4 | -4, -2, -1.
+        // The warm-up candle ends with E[T] = 4 and E[b * v] = 2.0, 0.0, -0.5, 1.75.
+        // The second candle has theta = -4, -6, -7 and E[b * v] = -1.125, -1.5625, -1.28125,
+        // so |theta| = 6 stays below E[T] * |E[b * v]| = 6.25, while |theta| = 7 exceeds 5.125
+        let trades = [
+            trade(0, 1.0, 2.0),
+            trade(1, 2.0, -1.0),
+            trade(2, 0.5, -2.0),
+            trade(3, 2.0, 2.0),
+            trade(4, 2.0, -2.0),
+            trade(5, 0.5, -4.0),
+            trade(6, 1.0, -1.0),
+        ];
+        for (i, t) in trades.iter().enumerate() {
+            let decision = rule.should_trigger(t, &OhlcCandle::default());
+            assert_eq!(decision.is_close(), i == 3 || i == 6, "trade {}", i);
+            if i == 3 {
+                assert_eq!(rule.estimator.threshold(), 4.0 * 1.75);
+            }
+        }
+
+        // E[T] = 4 + 0.5 * (3 - 4) after the candle of 3 ticks
+        assert_eq!(rule.estimator.threshold(), 3.5 * 1.28125);
+    }
+}
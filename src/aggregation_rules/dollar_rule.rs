@@ -0,0 +1,129 @@
+use crate::{
+    AggregationRule, ContractType, Error, ModularCandle, Result, TakerTrade, TriggerDecision,
+};
+
+/// Creates candles every n units of notional value traded,
+/// also known as dollar bars
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DollarRule {
+    // If true, the cumulative notional value needs to be reset
+    init: bool,
+
+    // See docs on ContractType enum for details
+    contract_type: ContractType,
+
+    // cumulative notional value
+    cum_notional: f64,
+
+    // The threshold notional value the candle needs to have before finishing it
+    threshold_notional: f64,
+}
+
+impl DollarRule {
+    /// Create a new instance with the given notional value threshold
+    ///
+    /// # Arguments:
+    /// `threshold_notional`: The notional value traded after which a new candle is created
+    /// `contract_type`: Determines how the notional value of each trade is computed
+    ///
+    pub fn new(threshold_notional: f64, contract_type: ContractType) -> Result<Self> {
+        if threshold_notional <= 0.0 {
+            return Err(Error::InvalidParam);
+        }
+        Ok(Self {
+            init: true,
+            contract_type,
+            cum_notional: 0.0,
+            threshold_notional,
+        })
+    }
+}
+
+impl<C, T> AggregationRule<C, T> for DollarRule
+where
+    C: ModularCandle<T>,
+    T: TakerTrade,
+{
+    fn should_trigger(&mut self, trade: &T, _candle: &C) -> TriggerDecision {
+        if self.init {
+            self.cum_notional = 0.0;
+            self.init = false;
+        }
+        self.cum_notional += self.contract_type.notional(trade);
+
+        let should_trigger = self.cum_notional > self.threshold_notional;
+        if should_trigger {
+            self.init = true;
+        }
+
+        should_trigger.into()
+    }
+
+    fn reset(&mut self) {
+        self.init = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{plot::OhlcCandle, Trade};
+
+    #[test]
+    fn dollar_rule_invalid_param() {
+        assert!(DollarRule::new(0.0, ContractType::Linear).is_err());
+        assert!(DollarRule::new(-1.0, ContractType::Inverse).is_err());
+    }
+
+    #[test]
+    fn dollar_rule() {
+        let mut rule = DollarRule::new(1000.0, ContractType::Linear).unwrap();
+        let candle = OhlcCandle::default();
+
+        // 2.0 * 300.0 = 600 notional
+        let t = Trade {
+            timestamp: 0,
+            price: 300.0,
+            size: 2.0,
+        };
+        assert_eq!(rule.should_trigger(&t, &candle), TriggerDecision::Continue);
+        // sells count towards the notional value as well
+        let t = Trade {
+            timestamp: 1,
+            price: 300.0,
+            size: -2.0,
+        };
+        assert_eq!(
+            rule.should_trigger(&t, &candle),
+            TriggerDecision::CloseIncluding
+        );
+
+        // The cumulative notional value is reset after a trigger
+        let t = Trade {
+            timestamp: 2,
+            price: 300.0,
+            size: 2.0,
+        };
+        assert_eq!(rule.should_trigger(&t, &candle), TriggerDecision::Continue);
+    }
+
+    #[test]
+    fn dollar_rule_inverse() {
+        let mut rule = DollarRule::new(1.0, ContractType::Inverse).unwrap();
+        let candle = OhlcCandle::default();
+
+        // 10_000 contracts at a price of 20_000 is 0.5 in Base currency
+        let t = Trade {
+            timestamp: 0,
+            price: 20_000.0,
+            size: 10_000.0,
+        };
+        assert_eq!(rule.should_trigger(&t, &candle), TriggerDecision::Continue);
+        assert_eq!(rule.should_trigger(&t, &candle), TriggerDecision::Continue);
+        assert_eq!(
+            rule.should_trigger(&t, &candle),
+            TriggerDecision::CloseIncluding
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{ewma::Ewma, Error, Result};
+
+/// Keeps track of the number of ticks in the current candle
+/// and the exponentially weighted expected number of ticks per candle `E[T]`,
+/// used by the adaptive information driven rules.
+/// Also handles the warm-up phase and the bounds of the candle length.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct ExpectedBarLength {
+    // number of ticks in the current candle
+    ticks: usize,
+
+    // the expected number of ticks per candle, E[T]
+    expected_ticks: Ewma,
+
+    // The number of ticks of a candle is bounded,
+    // as the adaptive threshold is prone to collapse or explode otherwise
+    min_ticks: usize,
+    max_ticks: usize,
+
+    // the number of candles that are created every `warmup_ticks` ticks,
+    // before the adaptive threshold takes over
+    warmup_bars: usize,
+    warmup_ticks: usize,
+
+    // The number of candles created so far
+    bars: usize,
+}
+
+impl ExpectedBarLength {
+    /// Create a new instance.
+    ///
+    /// # Arguments:
+    /// `expected_ticks_init`: The initial expected number of ticks per candle, used during warm-up
+    /// `ticks_bounds`: The (min, max) number of ticks per candle
+    /// `bar_len_span`: The span in candles of the EWMA estimating the expected candle length
+    /// `warmup_bars`: The number of candles created every `expected_ticks_init` ticks
+    ///
+    pub(crate) fn new(
+        expected_ticks_init: usize,
+        ticks_bounds: (usize, usize),
+        bar_len_span: usize,
+        warmup_bars: usize,
+    ) -> Result<Self> {
+        let (min_ticks, max_ticks) = ticks_bounds;
+        if min_ticks == 0
+            || expected_ticks_init < min_ticks
+            || expected_ticks_init > max_ticks
+            || bar_len_span == 0
+        {
+            return Err(Error::InvalidParam);
+        }
+        Ok(Self {
+            ticks: 0,
+            expected_ticks: Ewma::with_initial_value(bar_len_span, expected_ticks_init as f64),
+            min_ticks,
+            max_ticks,
+            warmup_bars,
+            warmup_ticks: expected_ticks_init,
+            bars: 0,
+        })
+    }
+
+    /// Resets the number of ticks in the current candle
+    pub(crate) fn reset(&mut self) {
+        self.ticks = 0;
+    }
+
+    /// Registers a new tick in the current candle
+    pub(crate) fn tick(&mut self) {
+        self.ticks += 1;
+    }
+
+    /// The expected number of ticks per candle, `E[T]`
+    pub(crate) fn expected_ticks(&self) -> f64 {
+        self.expected_ticks.value()
+    }
+
+    /// Decides whether the current candle is finished,
+    /// given whether the adaptive threshold has been exceeded.
+    /// The threshold is ignored during warm-up and outside the bounds of the candle length.
+    /// If the candle is finished, its length is used to update `E[T]`.
+    pub(crate) fn should_trigger(&mut self, threshold_exceeded: bool) -> bool {
+        let should_trigger = if self.bars < self.warmup_bars {
+            self.ticks >= self.warmup_ticks
+        } else {
+            self.ticks >= self.max_ticks || (self.ticks >= self.min_ticks && threshold_exceeded)
+        };
+        if should_trigger {
+            self.expected_ticks.add(self.ticks as f64);
+            self.bars += 1;
+        }
+
+        should_trigger
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expected_bar_length_invalid_params() {
+        assert!(ExpectedBarLength::new(0, (0, 10), 10, 1).is_err());
+        assert!(ExpectedBarLength::new(10, (20, 30), 10, 1).is_err());
+        assert!(ExpectedBarLength::new(40, (20, 30), 10, 1).is_err());
+        assert!(ExpectedBarLength::new(10, (1, 100), 0, 1).is_err());
+        assert!(ExpectedBarLength::new(10, (1, 100), 10, 0).is_ok());
+    }
+
+    #[test]
+    fn expected_bar_length() {
+        let mut len = ExpectedBarLength::new(5, (3, 8), 3, 1).unwrap();
+
+        // During warm-up, the threshold is ignored
+        for i in 0..5 {
+            len.tick();
+            assert_eq!(len.should_trigger(true), i == 4);
+        }
+        assert_eq!(len.expected_ticks(), 5.0);
+
+        // The lower bound
+        len.reset();
+        for i in 0..3 {
+            len.tick();
+            assert_eq!(len.should_trigger(true), i == 2);
+        }
+        assert_eq!(len.expected_ticks(), 4.0);
+
+        // The upper bound
+        len.reset();
+        for i in 0..8 {
+            len.tick();
+            assert_eq!(len.should_trigger(false), i == 7);
+        }
+        assert_eq!(len.expected_ticks(), 6.0);
+    }
+}
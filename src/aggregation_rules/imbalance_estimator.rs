@@ -0,0 +1,196 @@
+use super::expected_bar_length::ExpectedBarLength;
+use crate::{ewma::Ewma, Error, Result, TakerTrade};
+
+/// The direction of a taker trade, based on the sign of its size.
+/// 1.0 for buys, -1.0 for sells and 0.0 if the size is zero.
+#[inline(always)]
+pub(crate) fn trade_sign<T: TakerTrade>(trade: &T) -> f64 {
+    if trade.size() > 0.0 {
+        1.0
+    } else if trade.size() < 0.0 {
+        -1.0
+    } else {
+        0.0
+    }
+}
+
+/// Keeps track of the cumulative signed imbalance `theta` of the current candle,
+/// and decides when it exceeds its expected value `E[T] * |E[b]|`,
+/// where `E[T]` is the exponentially weighted expected candle length in ticks
+/// and `E[b]` is the exponentially weighted expected signed imbalance of a single tick.
+/// See "Advances in Financial Machine Learning" by Marcos Lopez de Prado, chapter 2.3.2.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct ImbalanceEstimator {
+    // If true, the imbalance of the current candle needs to be reset
+    init: bool,
+
+    // cumulative signed imbalance of the current candle
+    theta: f64,
+
+    // the expected signed imbalance per tick, E[b]
+    expected_imbalance: Ewma,
+
+    // Keeps track of E[T]
+    bar_length: ExpectedBarLength,
+}
+
+impl ImbalanceEstimator {
+    /// Create a new instance.
+    ///
+    /// # Arguments:
+    /// `expected_ticks_init`: The initial expected number of ticks per candle, used during warm-up
+    /// `ticks_bounds`: The (min, max) number of ticks per candle
+    /// `bar_len_span`: The span in candles of the EWMA estimating the expected candle length
+    /// `imbalance_span`: The span in ticks of the EWMA estimating the expected imbalance per tick
+    /// `warmup_bars`: The number of candles created every `expected_ticks_init` ticks
+    /// while the expected imbalance warms up
+    ///
+    pub(crate) fn new(
+        expected_ticks_init: usize,
+        ticks_bounds: (usize, usize),
+        bar_len_span: usize,
+        imbalance_span: usize,
+        warmup_bars: usize,
+    ) -> Result<Self> {
+        if imbalance_span == 0 {
+            return Err(Error::InvalidParam);
+        }
+        Ok(Self {
+            init: true,
+            theta: 0.0,
+            expected_imbalance: Ewma::new(imbalance_span),
+            bar_length: ExpectedBarLength::new(
+                expected_ticks_init,
+                ticks_bounds,
+                bar_len_span,
+                warmup_bars,
+            )?,
+        })
+    }
+
+    /// Discards the current candle, such that the next tick starts a new one
+    pub(crate) fn reset(&mut self) {
+        self.init = true;
+    }
+
+    /// Updates the estimator with the signed imbalance of the newest tick
+    ///
+    /// # Returns:
+    /// true if the imbalance of the current candle exceeds its expected value,
+    /// or the candle reached its maximum number of ticks
+    pub(crate) fn update(&mut self, signed_imbalance: f64) -> bool {
+        if self.init {
+            self.theta = 0.0;
+            self.bar_length.reset();
+            self.init = false;
+        }
+        self.theta += signed_imbalance;
+        self.bar_length.tick();
+        self.expected_imbalance.add(signed_imbalance);
+
+        let threshold_exceeded = self.theta.abs() >= self.threshold();
+        let should_trigger = self.bar_length.should_trigger(threshold_exceeded);
+        if should_trigger {
+            self.init = true;
+        }
+
+        should_trigger
+    }
+
+    /// The current expected absolute imbalance of a candle, `E[T] * |E[b]|`
+    pub(crate) fn threshold(&self) -> f64 {
+        self.bar_length.expected_ticks() * self.expected_imbalance.value().abs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imbalance_estimator_invalid_params() {
+        assert!(ImbalanceEstimator::new(0, (0, 10), 10, 10, 1).is_err());
+        assert!(ImbalanceEstimator::new(10, (1, 100), 10, 0, 1).is_err());
+        assert!(ImbalanceEstimator::new(10, (1, 100), 10, 10, 0).is_ok());
+    }
+
+    #[test]
+    fn imbalance_estimator_warmup() {
+        let mut est = ImbalanceEstimator::new(5, (1, 100), 10, 10, 2).unwrap();
+
+        // During warm-up, candles are created every 5 ticks regardless of imbalance
+        for _ in 0..2 {
+            for i in 0..5 {
+                let sign = if i % 2 == 0 { 1.0 } else { -1.0 };
+                assert_eq!(est.update(sign), i == 4);
+            }
+        }
+    }
+
+    #[test]
+    fn imbalance_estimator_one_sided() {
+        let mut est = ImbalanceEstimator::new(5, (1, 100), 10, 10, 0).unwrap();
+
+        // With only buys, the expected imbalance per tick is 1.0,
+        // so a candle is created every E[T] = 5 ticks
+        for _ in 0..10 {
+            for i in 0..5 {
+                assert_eq!(est.update(1.0), i == 4);
+            }
+            assert_eq!(est.threshold(), 5.0);
+        }
+    }
+
+    #[test]
+    fn imbalance_estimator_bounds() {
+        // Without any imbalance, the threshold is zero,
+        // so the candle is only created at the lower bound
+        let mut est = ImbalanceEstimator::new(5, (3, 8), 10, 10, 0).unwrap();
+        for _ in 0..10 {
+            for i in 0..3 {
+                assert_eq!(est.update(0.0), i == 2);
+            }
+        }
+
+        // A single buy followed by trades without any imbalance never exceeds the threshold,
+        // so the candles are created at the upper bound
+        let mut est = ImbalanceEstimator::new(3, (1, 3), 10, 10, 0).unwrap();
+        assert!(!est.update(1.0));
+        assert!(!est.update(0.0));
+        assert!(est.update(0.0));
+        for _ in 0..10 {
+            for i in 0..3 {
+                assert_eq!(est.update(0.0), i == 2);
+            }
+        }
+    }
+
+    #[test]
+    fn imbalance_estimator_threshold() {
+        // Both EWMAs use alpha = 0.5
+        let mut est = ImbalanceEstimator::new(4, (2, 10), 3, 3, 1).unwrap();
+
+        // Warm-up candle of 4 ticks, E[b] = 1.0, 0.0, -0.5, 0.25
+        for (sign, expected) in [(1.0, false), (-1.0, false), (-1.0, false), (1.0, true)] {
+            assert_eq!(est.update(sign), expected);
+        }
+        // E[T] = 4.0, E[b] = 0.25
+        assert_eq!(est.threshold(), 1.0);
+
+        // theta = -1, E[b] = -0.375, below the minimum of 2 ticks
+        assert!(!est.update(-1.0));
+        assert_eq!(est.threshold(), 1.5);
+        // theta = -2, E[b] = -0.6875
+        assert!(!est.update(-1.0));
+        assert_eq!(est.threshold(), 2.75);
+        // theta = -1, E[b] = 0.15625, so |theta| exceeds the threshold after 3 ticks
+        assert!(est.update(1.0));
+        assert_eq!(est.threshold(), 0.15625 * 3.5);
+
+        // The imbalance of the next candle starts from zero again,
+        // theta = 1, E[b] = 0.578125, E[T] = 3.5
+        assert!(!est.update(1.0));
+        assert_eq!(est.threshold(), 0.578125 * 3.5);
+    }
+}
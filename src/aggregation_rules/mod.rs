@@ -1,13 +1,36 @@
 mod aggregation_rule_trait;
 mod aligned_time_rule;
+mod candle_independent_rule_trait;
+mod combinators;
+mod dollar_imbalance_rule;
+mod dollar_rule;
+mod dollar_run_rule;
+mod expected_bar_length;
+mod imbalance_estimator;
+mod periodic_rule_trait;
 mod relative_price_rule;
+mod run_estimator;
+mod tick_imbalance_rule;
 mod tick_rule;
+mod tick_run_rule;
 mod time_rule;
+mod volume_imbalance_rule;
 mod volume_rule;
+mod volume_run_rule;
 
-pub use aggregation_rule_trait::AggregationRule;
+pub use aggregation_rule_trait::{AggregationRule, TriggerDecision};
 pub use aligned_time_rule::*;
+pub use candle_independent_rule_trait::CandleIndependentRule;
+pub use combinators::{AllOf, AnyOf, Not};
+pub use dollar_imbalance_rule::DollarImbalanceRule;
+pub use dollar_rule::DollarRule;
+pub use dollar_run_rule::DollarRunRule;
+pub use periodic_rule_trait::PeriodicRule;
 pub use relative_price_rule::RelativePriceRule;
+pub use tick_imbalance_rule::TickImbalanceRule;
 pub use tick_rule::TickRule;
+pub use tick_run_rule::TickRunRule;
 pub use time_rule::*;
+pub use volume_imbalance_rule::VolumeImbalanceRule;
 pub use volume_rule::VolumeRule;
+pub use volume_run_rule::VolumeRunRule;
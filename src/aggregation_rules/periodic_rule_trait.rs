@@ -0,0 +1,10 @@
+/// Implemented by aggregation rules that create candles on a regular time grid,
+/// which allows the periods without any trades to be identified,
+/// see `GapFillingAggregator`
+pub trait PeriodicRule {
+    /// The length of a period, in the resolution of the trade timestamps
+    fn period(&self) -> i64;
+
+    /// The timestamp at which the period of the current candle started
+    fn reference_timestamp(&self) -> i64;
+}
@@ -1,7 +1,8 @@
-use crate::{AggregationRule, Error, ModularCandle, Result, TakerTrade};
+use crate::{AggregationRule, Error, ModularCandle, Result, TakerTrade, TriggerDecision};
 
 /// Creates Candles once the price changed by a give relative absolute price delta
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RelativePriceRule {
     init: bool,
     init_price: f64,
@@ -31,20 +32,24 @@ where
     C: ModularCandle<T>,
     T: TakerTrade,
 {
-    fn should_trigger(&mut self, trade: &T, _candle: &C) -> bool {
+    fn should_trigger(&mut self, trade: &T, _candle: &C) -> TriggerDecision {
         if self.init {
             self.init = false;
             self.init_price = trade.price();
-            return false;
+            return TriggerDecision::Continue;
         }
 
         let price_delta = (trade.price() - self.init_price).abs() / self.init_price;
 
         if price_delta >= self.threshold_fraction {
             self.init_price = trade.price();
-            return true;
+            return TriggerDecision::CloseIncluding;
         }
-        false
+        TriggerDecision::Continue
+    }
+
+    fn reset(&mut self) {
+        self.init = true;
     }
 }
 
@@ -70,7 +75,7 @@ mod tests {
                 },
                 &OhlcCandle::default()
             ),
-            false
+            TriggerDecision::Continue
         );
         assert_eq!(
             rule.should_trigger(
@@ -81,7 +86,7 @@ mod tests {
                 },
                 &OhlcCandle::default()
             ),
-            false
+            TriggerDecision::Continue
         );
         assert_eq!(
             rule.should_trigger(
@@ -92,7 +97,7 @@ mod tests {
                 },
                 &OhlcCandle::default()
             ),
-            true
+            TriggerDecision::CloseIncluding
         );
         assert_eq!(
             rule.should_trigger(
@@ -103,7 +108,7 @@ mod tests {
                 },
                 &OhlcCandle::default()
             ),
-            false
+            TriggerDecision::Continue
         );
         assert_eq!(
             rule.should_trigger(
@@ -114,7 +119,7 @@ mod tests {
                 },
                 &OhlcCandle::default()
             ),
-            true
+            TriggerDecision::CloseIncluding
         );
     }
 
@@ -0,0 +1,165 @@
+use super::expected_bar_length::ExpectedBarLength;
+use crate::{ewma::Ewma, Error, Result};
+
+/// Keeps track of the cumulative buy and sell runs of the current candle,
+/// and decides when the larger run `theta = max(sum(v | b = 1), sum(v | b = -1))`
+/// exceeds its expected value `E[T] * max(P[b = 1] * E[v | b = 1], (1 - P[b = 1]) * E[v | b = -1])`,
+/// where `E[T]` is the exponentially weighted expected candle length in ticks,
+/// `P[b = 1]` the exponentially weighted probability of a tick being a buy
+/// and `E[v | b]` the exponentially weighted expected volume of a buy or sell tick.
+/// See "Advances in Financial Machine Learning" by Marcos Lopez de Prado, chapter 2.3.2.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct RunEstimator {
+    // If true, the runs of the current candle need to be reset
+    init: bool,
+
+    // cumulative volume of buys in the current candle
+    buy_run: f64,
+
+    // cumulative volume of sells in the current candle
+    sell_run: f64,
+
+    // the probability of a tick being a buy, P[b = 1]
+    prob_buy: Ewma,
+
+    // the expected volume of a buy tick, E[v | b = 1]
+    expected_buy_volume: Ewma,
+
+    // the expected volume of a sell tick, E[v | b = -1]
+    expected_sell_volume: Ewma,
+
+    // Keeps track of E[T]
+    bar_length: ExpectedBarLength,
+}
+
+impl RunEstimator {
+    /// Create a new instance.
+    ///
+    /// # Arguments:
+    /// `expected_ticks_init`: The initial expected number of ticks per candle, used during warm-up
+    /// `ticks_bounds`: The (min, max) number of ticks per candle
+    /// `bar_len_span`: The span in candles of the EWMA estimating the expected candle length
+    /// `run_span`: The span in ticks of the EWMAs estimating the probability of a buy
+    /// and the expected volume of buys and sells
+    /// `warmup_bars`: The number of candles created every `expected_ticks_init` ticks
+    /// while the expected runs warm up
+    ///
+    pub(crate) fn new(
+        expected_ticks_init: usize,
+        ticks_bounds: (usize, usize),
+        bar_len_span: usize,
+        run_span: usize,
+        warmup_bars: usize,
+    ) -> Result<Self> {
+        if run_span == 0 {
+            return Err(Error::InvalidParam);
+        }
+        Ok(Self {
+            init: true,
+            buy_run: 0.0,
+            sell_run: 0.0,
+            prob_buy: Ewma::new(run_span),
+            expected_buy_volume: Ewma::new(run_span),
+            expected_sell_volume: Ewma::new(run_span),
+            bar_length: ExpectedBarLength::new(
+                expected_ticks_init,
+                ticks_bounds,
+                bar_len_span,
+                warmup_bars,
+            )?,
+        })
+    }
+
+    /// Discards the current candle, such that the next tick starts a new one
+    pub(crate) fn reset(&mut self) {
+        self.init = true;
+    }
+
+    /// Updates the estimator with the newest tick
+    ///
+    /// # Arguments:
+    /// `sign`: The direction of the tick, see `trade_sign`
+    /// `volume`: The absolute volume of the tick
+    ///
+    /// # Returns:
+    /// true if the larger run of the current candle exceeds its expected value,
+    /// or the candle reached its maximum number of ticks
+    pub(crate) fn update(&mut self, sign: f64, volume: f64) -> bool {
+        if self.init {
+            self.buy_run = 0.0;
+            self.sell_run = 0.0;
+            self.bar_length.reset();
+            self.init = false;
+        }
+        if sign > 0.0 {
+            self.buy_run += volume;
+            self.prob_buy.add(1.0);
+            self.expected_buy_volume.add(volume);
+        } else if sign < 0.0 {
+            self.sell_run += volume;
+            self.prob_buy.add(0.0);
+            self.expected_sell_volume.add(volume);
+        }
+        self.bar_length.tick();
+
+        let threshold_exceeded = self.buy_run.max(self.sell_run) >= self.threshold();
+        let should_trigger = self.bar_length.should_trigger(threshold_exceeded);
+        if should_trigger {
+            self.init = true;
+        }
+
+        should_trigger
+    }
+
+    /// The current expected run of a candle,
+    /// `E[T] * max(P[b = 1] * E[v | b = 1], (1 - P[b = 1]) * E[v | b = -1])`
+    pub(crate) fn threshold(&self) -> f64 {
+        let p = self.prob_buy.value();
+        let expected_run = (p * self.expected_buy_volume.value())
+            .max((1.0 - p) * self.expected_sell_volume.value());
+        self.bar_length.expected_ticks() * expected_run
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_estimator_invalid_params() {
+        assert!(RunEstimator::new(0, (0, 10), 10, 10, 1).is_err());
+        assert!(RunEstimator::new(10, (1, 100), 10, 0, 1).is_err());
+        assert!(RunEstimator::new(10, (1, 100), 10, 10, 0).is_ok());
+    }
+
+    #[test]
+    fn run_estimator_one_sided() {
+        let mut est = RunEstimator::new(5, (1, 100), 10, 10, 0).unwrap();
+
+        // With only buys of equal volume, P[b = 1] = 1 and E[v | b = 1] = 2,
+        // so a candle is created every E[T] = 5 ticks
+        for _ in 0..10 {
+            for i in 0..5 {
+                assert_eq!(est.update(1.0, 2.0), i == 4);
+            }
+            assert_eq!(est.threshold(), 10.0);
+        }
+    }
+
+    #[test]
+    fn run_estimator_balanced() {
+        let mut est = RunEstimator::new(4, (1, 100), 1, 10, 0).unwrap();
+
+        // Contrary to imbalance bars, the runs of alternating buys and sells do not cancel out,
+        // so the candles are created well before reaching the upper bound
+        let mut num_candles = 0;
+        for i in 0..1000 {
+            let sign = if i % 2 == 0 { 1.0 } else { -1.0 };
+            if est.update(sign, 1.0) {
+                num_candles += 1;
+            }
+        }
+        assert!(num_candles > 100);
+    }
+}
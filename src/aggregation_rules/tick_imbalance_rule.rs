@@ -0,0 +1,128 @@
+use super::imbalance_estimator::{trade_sign, ImbalanceEstimator};
+use crate::{AggregationRule, ModularCandle, Result, TakerTrade, TriggerDecision};
+
+/// Creates candles once the tick imbalance exceeds its expected value,
+/// also known as tick imbalance bars.
+/// Each tick contributes +1 for a buy and -1 for a sell, based on the sign of `TakerTrade::size()`.
+/// A candle is finished once the absolute cumulative imbalance |θ_T| exceeds `E[T] * |E[b]|`,
+/// where both the expected candle length `E[T]` and the expected imbalance per tick `E[b]`
+/// are estimated using exponentially weighted moving averages.
+/// See "Advances in Financial Machine Learning" by Marcos Lopez de Prado, chapter 2.3.2.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TickImbalanceRule {
+    estimator: ImbalanceEstimator,
+}
+
+impl TickImbalanceRule {
+    /// Create a new instance of the `TickImbalanceRule`
+    ///
+    /// # Arguments:
+    /// `expected_ticks_init`: The initial expected number of ticks per candle, used during warm-up
+    /// `ticks_bounds`: The (min, max) number of ticks per candle,
+    /// which keeps the adaptive threshold from collapsing or exploding
+    /// `bar_len_span`: The span in candles of the EWMA estimating the expected candle length
+    /// `imbalance_span`: The span in ticks of the EWMA estimating the expected imbalance per tick
+    /// `warmup_bars`: The number of candles created every `expected_ticks_init` ticks,
+    /// while the expected imbalance warms up
+    ///
+    pub fn new(
+        expected_ticks_init: usize,
+        ticks_bounds: (usize, usize),
+        bar_len_span: usize,
+        imbalance_span: usize,
+        warmup_bars: usize,
+    ) -> Result<Self> {
+        Ok(Self {
+            estimator: ImbalanceEstimator::new(
+                expected_ticks_init,
+                ticks_bounds,
+                bar_len_span,
+                imbalance_span,
+                warmup_bars,
+            )?,
+        })
+    }
+}
+
+impl<C, T> AggregationRule<C, T> for TickImbalanceRule
+where
+    C: ModularCandle<T>,
+    T: TakerTrade,
+{
+    fn should_trigger(&mut self, trade: &T, _candle: &C) -> TriggerDecision {
+        self.estimator.update(trade_sign(trade)).into()
+    }
+
+    fn reset(&mut self) {
+        self.estimator.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use trade_aggregation_derive::Candle;
+
+    use crate::{
+        aggregate_all_trades,
+        candle_components::{CandleComponent, CandleComponentUpdate, NumTrades, Open},
+        load_trades_from_csv,
+        plot::OhlcCandle,
+        BoundaryPolicy, GenericAggregator, Trade,
+    };
+
+    #[derive(Debug, Default, Clone, Candle)]
+    struct MyCandle {
+        open: Open,
+        num_trades: NumTrades<u32>,
+    }
+
+    #[test]
+    fn tick_imbalance_rule_invalid_params() {
+        assert!(TickImbalanceRule::new(0, (0, 1000), 10, 100, 10).is_err());
+        assert!(TickImbalanceRule::new(100, (10, 1000), 0, 100, 10).is_err());
+        assert!(TickImbalanceRule::new(100, (10, 1000), 10, 0, 10).is_err());
+    }
+
+    #[test]
+    fn tick_imbalance_rule() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+
+        let rule = TickImbalanceRule::new(1000, (100, 10_000), 10, 10_000, 10).unwrap();
+        let mut aggregator = GenericAggregator::<OhlcCandle, TickImbalanceRule, Trade>::new(rule);
+        let candles = aggregate_all_trades(&trades, &mut aggregator);
+        // The number of ticks per candle is bounded to [100, 10_000]
+        assert!(candles.len() >= trades.len() / 10_000);
+        assert!(candles.len() <= trades.len() / 100);
+    }
+
+    #[test]
+    fn tick_imbalance_rule_hand_built() {
+        // Both EWMAs use alpha = 0.5, the first candle is a warm-up candle of 4 ticks
+        let rule = TickImbalanceRule::new(4, (2, 10), 3, 3, 1).unwrap();
+        let mut aggregator =
+            GenericAggregator::<MyCandle, TickImbalanceRule, Trade>::with_boundary_policy(
+                rule,
+                BoundaryPolicy::Closing,
+            );
+
+        // After the warm-up E[T] = 4 and E[b] = 0.25,
+        // the second candle closes once |theta| = 1 exceeds E[T] * |E[b]| = 3.5 * 0.15625
+        let trades: Vec<Trade> = [1.0, -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0]
+            .iter()
+            .enumerate()
+            .map(|(i, size)| Trade {
+                timestamp: i as i64,
+                price: 100.0 + i as f64,
+                size: *size,
+            })
+            .collect();
+        let candles = aggregate_all_trades(&trades, &mut aggregator);
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[0].num_trades(), 4);
+        assert_eq!(candles[0].open(), 100.0);
+        assert_eq!(candles[1].num_trades(), 3);
+        assert_eq!(candles[1].open(), 104.0);
+    }
+}
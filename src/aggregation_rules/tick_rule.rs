@@ -1,7 +1,8 @@
-use crate::{AggregationRule, ModularCandle, TakerTrade};
+use crate::{AggregationRule, CandleIndependentRule, ModularCandle, TakerTrade, TriggerDecision};
 
 /// Creates candles every n ticks
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TickRule {
     init: bool,
     tick_counter: usize,
@@ -28,7 +29,7 @@ where
     C: ModularCandle<T>,
     T: TakerTrade,
 {
-    fn should_trigger(&mut self, _trade: &T, _candle: &C) -> bool {
+    fn should_trigger(&mut self, _trade: &T, _candle: &C) -> TriggerDecision {
         if self.init {
             self.tick_counter = 0;
             self.init = false;
@@ -38,9 +39,26 @@ where
 
         if self.tick_counter >= self.n_ticks {
             self.init = true;
+            return TriggerDecision::CloseIncluding;
+        }
+        TriggerDecision::Continue
+    }
+
+    fn reset(&mut self) {
+        self.init = true;
+    }
+}
+
+impl CandleIndependentRule for TickRule {
+    /// Counts the trades since the last candle, as if every n-th trade closed one
+    fn skip_trades<T: TakerTrade>(&mut self, trades: &[T]) -> bool {
+        if trades.is_empty() {
             return true;
         }
-        false
+        let counted = if self.init { 0 } else { self.tick_counter };
+        self.tick_counter = (counted + trades.len()) % self.n_ticks.max(1);
+        self.init = self.tick_counter == 0;
+        true
     }
 }
 
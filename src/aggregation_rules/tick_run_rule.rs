@@ -0,0 +1,111 @@
+use super::{imbalance_estimator::trade_sign, run_estimator::RunEstimator};
+use crate::{AggregationRule, ModularCandle, Result, TakerTrade, TriggerDecision};
+
+/// Creates candles once the run of buys or sells exceeds its expected value,
+/// also known as tick run bars.
+/// The side of each tick is based on the sign of `TakerTrade::size()`.
+/// A candle is finished once the larger number of buys or sells in the candle
+/// exceeds `E[T] * max(P[b = 1], 1 - P[b = 1])`,
+/// where both the expected candle length `E[T]` and the probability of a buy `P[b = 1]`
+/// are estimated using exponentially weighted moving averages.
+/// See "Advances in Financial Machine Learning" by Marcos Lopez de Prado, chapter 2.3.2.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TickRunRule {
+    estimator: RunEstimator,
+}
+
+impl TickRunRule {
+    /// Create a new instance of the `TickRunRule`
+    ///
+    /// # Arguments:
+    /// `expected_ticks_init`: The initial expected number of ticks per candle, used during warm-up
+    /// `ticks_bounds`: The (min, max) number of ticks per candle,
+    /// which keeps the adaptive threshold from collapsing or exploding
+    /// `bar_len_span`: The span in candles of the EWMA estimating the expected candle length
+    /// `run_span`: The span in ticks of the EWMA estimating the probability of a buy
+    /// `warmup_bars`: The number of candles created every `expected_ticks_init` ticks,
+    /// while the expected run warms up
+    ///
+    pub fn new(
+        expected_ticks_init: usize,
+        ticks_bounds: (usize, usize),
+        bar_len_span: usize,
+        run_span: usize,
+        warmup_bars: usize,
+    ) -> Result<Self> {
+        Ok(Self {
+            estimator: RunEstimator::new(
+                expected_ticks_init,
+                ticks_bounds,
+                bar_len_span,
+                run_span,
+                warmup_bars,
+            )?,
+        })
+    }
+}
+
+impl<C, T> AggregationRule<C, T> for TickRunRule
+where
+    C: ModularCandle<T>,
+    T: TakerTrade,
+{
+    fn should_trigger(&mut self, trade: &T, _candle: &C) -> TriggerDecision {
+        self.estimator.update(trade_sign(trade), 1.0).into()
+    }
+
+    fn reset(&mut self) {
+        self.estimator.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aggregate_all_trades, load_trades_from_csv, plot::OhlcCandle, GenericAggregator, Trade,
+    };
+
+    fn trade(timestamp: i64, price: f64, size: f64) -> Trade {
+        Trade {
+            timestamp,
+            price,
+            size,
+        }
+    }
+
+    #[test]
+    fn tick_run_rule() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+
+        let rule = TickRunRule::new(1000, (100, 10_000), 10, 10_000, 10).unwrap();
+        let mut aggregator = GenericAggregator::<OhlcCandle, TickRunRule, Trade>::new(rule);
+        let candles = aggregate_all_trades(&trades, &mut aggregator);
+        // The number of ticks per candle is bounded to [100, 10_000]
+        assert!(candles.len() >= trades.len() / 10_000);
+        assert!(candles.len() <= trades.len() / 100);
+    }
+
+    #[test]
+    fn tick_run_rule_hand_built() {
+        // All EWMAs use alpha = 0.5, the first candle is a warm-up candle of 4 ticks
+        let mut rule = TickRunRule::new(4, (2, 10), 3, 3, 1).unwrap();
+
+        // The warm-up candle of buys ends with E[T] = 4 and P[b = 1] = 1.
+        // The second candle has a buy run of 2 after two buys, below E[T] * P[b = 1] = 4,
+        // the following sell lowers P[b = 1] to 0.5, so the buy run exceeds E[T] * 0.5 = 2
+        let sizes = [1.0, 1.0, 1.0, 1.0, 1.0, 1.0, -1.0];
+        for (i, size) in sizes.iter().enumerate() {
+            let decision =
+                rule.should_trigger(&trade(i as i64, 100.0, *size), &OhlcCandle::default());
+            assert_eq!(decision.is_close(), i == 3 || i == 6, "trade {}", i);
+            if i == 3 {
+                assert_eq!(rule.estimator.threshold(), 4.0);
+            }
+        }
+
+        // E[T] = 4 + 0.5 * (3 - 4) after the candle of 3 ticks, both runs are expected to be 0.5 ticks
+        assert_eq!(rule.estimator.threshold(), 3.5 * 0.5);
+    }
+}
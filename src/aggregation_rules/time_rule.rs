@@ -1,7 +1,11 @@
-use crate::{AggregationRule, ModularCandle, TakerTrade};
+use crate::{
+    AggregationRule, CandleIndependentRule, ModularCandle, PeriodicRule, TakerTrade,
+    TriggerDecision,
+};
 
 /// The resolution of the "TakerTrade" timestamps
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TimestampResolution {
     /// The timestamp of the TakerTrade is measured in seconds
     Second,
@@ -19,6 +23,7 @@ pub enum TimestampResolution {
 /// The classic time based aggregation rule,
 /// creating a new candle every n seconds
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TimeRule {
     /// If true, the reference timestamp needs to be reset
     init: bool,
@@ -26,6 +31,10 @@ pub struct TimeRule {
     // The timestamp this rule uses as a reference
     reference_timestamp: i64,
 
+    // If true, the reference timestamp belongs to the current candle,
+    // even if the next trade still needs to reset it, see `should_trigger_at`
+    has_reference: bool,
+
     // The period for the candle in seconds
     // constants can be used nicely here from constants.rs
     // e.g.: M1 -> 1 minute candles
@@ -51,6 +60,7 @@ impl TimeRule {
         Self {
             init: true,
             reference_timestamp: 0,
+            has_reference: false,
             period_s: period_s * ts_multiplier,
         }
     }
@@ -61,18 +71,77 @@ where
     C: ModularCandle<T>,
     T: TakerTrade,
 {
-    fn should_trigger(&mut self, trade: &T, _candle: &C) -> bool {
+    fn should_trigger(&mut self, trade: &T, _candle: &C) -> TriggerDecision {
         if self.init {
             self.reference_timestamp = trade.timestamp();
             self.init = false;
+            self.has_reference = true;
+        }
+        // A trade at or after the end of the period belongs to the next candle,
+        // so a candle never includes trades of the next period
+        if trade.timestamp() - self.reference_timestamp >= self.period_s {
+            // The trade opens the next candle, whose period starts with the following trade.
+            // Until then, the next period is measured from the trade opening it.
+            self.reference_timestamp = trade.timestamp();
+            self.init = true;
+            return TriggerDecision::CloseBefore;
         }
-        let should_trigger = trade.timestamp() - self.reference_timestamp > self.period_s;
+
+        TriggerDecision::Continue
+    }
+
+    fn should_trigger_at(&mut self, timestamp: i64, _candle: &C) -> bool {
+        if !self.has_reference {
+            return false;
+        }
+        let should_trigger = timestamp - self.reference_timestamp >= self.period_s;
         if should_trigger {
+            // There is no trade opening the next candle, so the next trade starts a new period
             self.init = true;
+            self.has_reference = false;
         }
 
         should_trigger
     }
+
+    fn reset(&mut self) {
+        self.init = true;
+        self.has_reference = false;
+    }
+}
+
+impl PeriodicRule for TimeRule {
+    fn period(&self) -> i64 {
+        self.period_s
+    }
+
+    fn reference_timestamp(&self) -> i64 {
+        self.reference_timestamp
+    }
+}
+
+impl CandleIndependentRule for TimeRule {
+    /// Jumps from period to period, locating the trade closing each of them with a binary search,
+    /// which requires the trades to be sorted by their timestamps
+    fn skip_trades<T: TakerTrade>(&mut self, trades: &[T]) -> bool {
+        let mut i = 0;
+        while let Some(trade) = trades.get(i) {
+            if self.init {
+                self.reference_timestamp = trade.timestamp();
+                self.init = false;
+                self.has_reference = true;
+            }
+            let closing = i + trades[i..]
+                .partition_point(|t| t.timestamp() - self.reference_timestamp < self.period_s);
+            let Some(trade) = trades.get(closing) else {
+                break;
+            };
+            self.reference_timestamp = trade.timestamp();
+            self.init = true;
+            i = closing + 1;
+        }
+        true
+    }
 }
 
 #[cfg(test)]
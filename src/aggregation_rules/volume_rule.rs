@@ -1,7 +1,9 @@
-use crate::{AggregationRule, By, Error, ModularCandle, Result, TakerTrade};
+use crate::{AggregationRule, By, Error, ModularCandle, Result, TakerTrade, TriggerDecision};
 
-/// Creates candles every n units of volume traded
+/// Creates candles every n units of volume traded.
+/// In exact mode, see `VolumeRule::exact`, every candle contains exactly n units of volume.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VolumeRule {
     // If true, the cumulative volume needs to be reset
     init: bool,
@@ -14,6 +16,9 @@ pub struct VolumeRule {
 
     // The theshold volume the candle needs to have before finishing it
     threshold_vol: f64,
+
+    // If true, trades exceeding the threshold are split across candles
+    exact: bool,
 }
 
 impl VolumeRule {
@@ -27,6 +32,26 @@ impl VolumeRule {
             by,
             cum_vol: 0.0,
             threshold_vol,
+            exact: false,
+        })
+    }
+
+    /// Create a new instance in which every candle contains exactly the given volume.
+    /// A trade exceeding the threshold is split into a fill completing the current candle
+    /// and a remainder carried into the next candles, which may complete several candles on its own.
+    /// This requires a trade type supporting `TakerTrade::with_size`,
+    /// otherwise the trade is not split and the candle exceeds the threshold.
+    /// The same applies to trades without a finite volume, e.g.: with a size of NaN.
+    /// As a single trade can create multiple candles, use `Aggregator::update_into` to receive all of them at once.
+    ///
+    /// # Arguments:
+    /// `threshold_vol`: The volume each candle contains
+    /// `by`: Determines how the volume of each trade is computed
+    ///
+    pub fn exact(threshold_vol: f64, by: By) -> Result<Self> {
+        Ok(Self {
+            exact: true,
+            ..Self::new(threshold_vol, by)?
         })
     }
 }
@@ -36,21 +61,164 @@ where
     C: ModularCandle<T>,
     T: TakerTrade,
 {
-    fn should_trigger(&mut self, trade: &T, _candle: &C) -> bool {
+    fn should_trigger(&mut self, trade: &T, _candle: &C) -> TriggerDecision {
         if self.init {
             self.cum_vol = 0.0;
             self.init = false;
         }
-        self.cum_vol += match self.by {
-            By::Quote => trade.size().abs(),
-            By::Base => trade.size().abs() / trade.price(),
-        };
+        let volume = self.by.volume(trade);
+        if self.exact {
+            let remaining_vol = self.threshold_vol - self.cum_vol;
+            if volume < remaining_vol {
+                self.cum_vol += volume;
+                return TriggerDecision::Continue;
+            }
+            // The trade completes the candle, its remainder is evaluated again for the next candle
+            self.init = true;
+            let split = trade.size() * remaining_vol / volume;
+            // A trade without a finite volume, e.g.: with a size of NaN or a price of zero,
+            // can't be split, so it is included as a whole
+            if !split.is_finite() || split == 0.0 {
+                return TriggerDecision::CloseIncluding;
+            }
+            if split.abs() > trade.size().abs() {
+                return TriggerDecision::CloseAndSplit(trade.size());
+            }
+            return TriggerDecision::CloseAndSplit(split);
+        }
+        self.cum_vol += volume;
 
         let should_trigger = self.cum_vol > self.threshold_vol;
         if should_trigger {
             self.init = true;
         }
 
-        should_trigger
+        should_trigger.into()
+    }
+
+    fn reset(&mut self) {
+        self.init = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use trade_aggregation_derive::Candle;
+
+    use super::*;
+    use crate::{
+        aggregate_all_trades, aggregate_all_trades_with_partial,
+        candle_components::{
+            CandleComponent, CandleComponentUpdate, Close, NumTrades, Volume, WeightedPrice,
+        },
+        load_trades_from_csv, BoundaryPolicy, GenericAggregator, Trade,
+    };
+
+    #[derive(Debug, Default, Clone, Candle)]
+    struct MyCandle {
+        close: Close,
+        volume: Volume,
+        weighted_price: WeightedPrice,
+        num_trades: NumTrades<u32>,
+    }
+
+    #[test]
+    fn volume_rule_invalid_param() {
+        assert!(VolumeRule::new(0.0, By::Quote).is_err());
+        assert!(VolumeRule::exact(-1.0, By::Base).is_err());
+    }
+
+    #[test]
+    fn volume_rule_exact() {
+        let trade = |timestamp, price, size| Trade {
+            timestamp,
+            price,
+            size,
+        };
+        let trades = [
+            trade(0, 100.0, 4.0),
+            trade(1, 101.0, 3.0),
+            // completes the first candle, two more candles and leaves 2.0 for the fourth
+            trade(2, 102.0, 25.0),
+            trade(3, 103.0, -8.0),
+        ];
+        let rule = VolumeRule::exact(10.0, By::Quote).unwrap();
+        let mut aggregator = GenericAggregator::<MyCandle, VolumeRule, Trade>::new(rule);
+        let (candles, partial) = aggregate_all_trades_with_partial(&trades, &mut aggregator);
+        assert!(partial.is_none());
+
+        assert_eq!(candles.len(), 4);
+        for c in &candles {
+            assert_eq!(c.volume(), 10.0);
+        }
+        assert_eq!(
+            candles[0].weighted_price(),
+            (4.0 * 100.0 + 3.0 * 101.0 + 3.0 * 102.0) / 10.0
+        );
+        assert_eq!(candles[1].weighted_price(), 102.0);
+        assert_eq!(candles[2].num_trades(), 1);
+        assert_eq!(
+            candles[3].weighted_price(),
+            (2.0 * 102.0 + 8.0 * 103.0) / 10.0
+        );
+        assert_eq!(candles[3].close(), 103.0);
+    }
+
+    #[test]
+    fn volume_rule_exact_non_finite() {
+        let trade = |timestamp, price, size| Trade {
+            timestamp,
+            price,
+            size,
+        };
+
+        // A trade with a size of NaN completes the candle without being split,
+        // so it is only included in the closing candle
+        let trades = [
+            trade(0, 100.0, 4.0),
+            trade(1, 100.0, f64::NAN),
+            trade(2, 100.0, 3.0),
+        ];
+        let rule = VolumeRule::exact(10.0, By::Quote).unwrap();
+        let mut aggregator = GenericAggregator::<MyCandle, VolumeRule, Trade>::with_boundary_policy(
+            rule,
+            BoundaryPolicy::Closing,
+        );
+        let (candles, partial) = aggregate_all_trades_with_partial(&trades, &mut aggregator);
+        assert_eq!(candles.len(), 1);
+        assert_eq!(candles[0].num_trades(), 2);
+        assert_eq!(partial.unwrap().volume(), 3.0);
+
+        // A trade at a price of zero has an infinite base volume
+        let trades = [
+            trade(0, 100.0, 400.0),
+            trade(1, 0.0, 1.0),
+            trade(2, 100.0, 300.0),
+        ];
+        let rule = VolumeRule::exact(10.0, By::Base).unwrap();
+        let mut aggregator = GenericAggregator::<MyCandle, VolumeRule, Trade>::with_boundary_policy(
+            rule,
+            BoundaryPolicy::Closing,
+        );
+        let (candles, partial) = aggregate_all_trades_with_partial(&trades, &mut aggregator);
+        assert_eq!(candles.len(), 1);
+        assert_eq!(candles[0].num_trades(), 2);
+        assert_eq!(partial.unwrap().volume(), 300.0);
+    }
+
+    #[test]
+    fn volume_rule_exact_real_data() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+
+        let threshold = 1_000_000.0;
+        let rule = VolumeRule::exact(threshold, By::Quote).unwrap();
+        let mut aggregator = GenericAggregator::<MyCandle, VolumeRule, Trade>::new(rule);
+        let candles = aggregate_all_trades(&trades, &mut aggregator);
+
+        let total_volume: f64 = trades.iter().map(|t| t.size.abs()).sum();
+        assert_eq!(candles.len(), (total_volume / threshold) as usize);
+        for c in &candles {
+            assert!((c.volume() - threshold).abs() < 1e-6);
+        }
     }
 }
@@ -1,6 +1,6 @@
-use std::marker::PhantomData;
+use std::{collections::VecDeque, marker::PhantomData};
 
-use crate::{AggregationRule, ModularCandle, TakerTrade};
+use crate::{AggregationRule, ModularCandle, TakerTrade, TriggerDecision};
 
 /// Defines the needed methods for any online `Aggregator`
 pub trait Aggregator<Candle, T: TakerTrade> {
@@ -11,18 +11,125 @@ pub trait Aggregator<Candle, T: TakerTrade> {
     ///
     /// # Returns:
     /// Some output only when a new candle has been created,
-    /// otherwise it returns None
+    /// otherwise it returns None.
+    /// If a single trade creates multiple candles, e.g.: when splitting it,
+    /// the first one is returned and the others are kept,
+    /// such that the following calls to `update`, `advance_time` or `finish` return them in order.
+    /// Use `update_into` to receive all of them right away.
     fn update(&mut self, trade: &T) -> Option<Candle>;
+
+    /// Updates the aggregation state with a new trade,
+    /// pushing all candles that have been created by it into `candles`,
+    /// after the candles kept by previous calls to `update`, if any.
+    /// Contrary to `update`, this returns all candles created by a single trade at once,
+    /// e.g.: when filling the gaps of periods without any trades.
+    ///
+    /// # Arguments:
+    /// trade: the trade information to add to the aggregation process
+    /// candles: the created candles are appended to it
+    fn update_into(&mut self, trade: &T, candles: &mut Vec<Candle>) {
+        if let Some(candle) = self.update(trade) {
+            candles.push(candle);
+        }
+    }
+
+    /// Informs the aggregation state that time has advanced without a new trade,
+    /// such that time based rules can close a candle on schedule,
+    /// e.g.: driven by a wall clock in live trading.
+    /// Timestamps must not be earlier than the most recent trade.
+    ///
+    /// # Arguments:
+    /// timestamp: the current time, with the same resolution as the trade timestamps
+    ///
+    /// # Returns:
+    /// Some output only when a new candle has been created,
+    /// otherwise it returns None
+    fn advance_time(&mut self, _timestamp: i64) -> Option<Candle> {
+        None
+    }
+
+    /// Informs the aggregation state that time has advanced without a new trade,
+    /// pushing all candles that have been closed by it into `candles`, see `advance_time`
+    ///
+    /// # Arguments:
+    /// timestamp: the current time, with the same resolution as the trade timestamps
+    /// candles: the created candles are appended to it
+    fn advance_time_into(&mut self, timestamp: i64, candles: &mut Vec<Candle>) {
+        candles.extend(self.advance_time(timestamp));
+    }
+
+    /// Finishes the current aggregation period, e.g.: at the end of the input data.
+    /// The next trade starts a new aggregation period.
+    ///
+    /// # Returns:
+    /// The incomplete candle of the current aggregation period,
+    /// or None if it has not received any trades.
+    /// If candles kept by previous calls to `update` have not been returned yet,
+    /// the oldest of those is returned instead and the incomplete candle is kept for the following calls.
+    fn finish(&mut self) -> Option<Candle> {
+        None
+    }
+
+    /// Pushes the candles that have been completed, but not returned yet, into `candles`,
+    /// without finishing the current aggregation period,
+    /// e.g.: the candles kept by `update` or the candles completed by releasing buffered trades.
+    /// Calling `finish` afterwards only returns the incomplete candle.
+    ///
+    /// # Arguments:
+    /// candles: the completed candles are appended to it
+    fn flush_into(&mut self, _candles: &mut Vec<Candle>) {}
+
+    /// Finishes the current aggregation period, see `finish`,
+    /// pushing all candles that have been created by it into `candles`,
+    /// i.e.: the candles pushed by `flush_into`, followed by the incomplete candle, if any.
+    /// Contrary to `finish`, this allows finishing to complete further candles,
+    /// e.g.: when releasing buffered trades.
+    /// Use `flush_into` followed by `finish` to tell the incomplete candle apart.
+    ///
+    /// # Arguments:
+    /// candles: the created candles are appended to it
+    fn finish_into(&mut self, candles: &mut Vec<Candle>) {
+        candles.extend(self.finish());
+    }
+}
+
+/// Determines which candle includes the trade at the boundary of two candles,
+/// i.e.: the trade for which the aggregation rule decided `TriggerDecision::CloseIncluding`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BoundaryPolicy {
+    /// The trade is only included in the candle being closed
+    Closing,
+
+    /// The trade is only included in the next candle
+    Next,
+
+    /// The trade is included in both the candle being closed and the next candle,
+    /// which ensures the `Open` of a candle equals the `Close` of the previous one.
+    /// Note that components such as `Volume` and `NumTrades` count the trade twice.
+    #[default]
+    Both,
 }
 
 /// An `Aggregator` that is generic over
 /// the type of Candle being produced,
 /// as well as by which rule the candle is created
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GenericAggregator<C, R, T> {
     candle: C,
     aggregation_rule: R,
     trade_type: PhantomData<T>,
+
+    // Which candle includes the trade at the boundary
+    boundary_policy: BoundaryPolicy,
+
+    // Whether the current candle has received any trades
+    has_trades: bool,
+
+    // The candles created, but not yet returned by `update`, `advance_time` or `finish`,
+    // as a single trade may create multiple candles
+    pending: VecDeque<C>,
 }
 
 impl<C, R, T> GenericAggregator<C, R, T>
@@ -32,12 +139,160 @@ where
     T: TakerTrade,
 {
     /// Create a new instance with a concrete aggregation rule
-    /// and a default candle
+    /// and a default candle,
+    /// including the trade at the boundary in both candles
     pub fn new(aggregation_rule: R) -> Self {
+        Self::with_boundary_policy(aggregation_rule, BoundaryPolicy::default())
+    }
+
+    /// Create a new instance with a concrete aggregation rule
+    /// and a default candle
+    ///
+    /// # Arguments:
+    /// aggregation_rule: Decides when a candle is finished
+    /// boundary_policy: Which candle includes the trade at the boundary
+    ///
+    pub fn with_boundary_policy(aggregation_rule: R, boundary_policy: BoundaryPolicy) -> Self {
         Self {
             candle: Default::default(),
             aggregation_rule,
             trade_type: PhantomData,
+            boundary_policy,
+            has_trades: false,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// The aggregation rule used by this aggregator
+    pub(crate) fn aggregation_rule(&self) -> &R {
+        &self.aggregation_rule
+    }
+
+    /// Takes the current candle, if it has received any trades, and starts a new one
+    fn take_candle(&mut self) -> Option<C> {
+        // An empty candle is never emitted
+        if !self.has_trades {
+            return None;
+        }
+        let candle = self.candle.clone();
+        self.candle.reset();
+        self.has_trades = false;
+        Some(candle)
+    }
+
+    fn update_candle(&mut self, trade: &T) {
+        self.candle.update(trade);
+        self.has_trades = true;
+    }
+
+    /// Process a trade, passing all candles created by it to `on_candle`
+    fn process<F: FnMut(C)>(&mut self, trade: &T, mut on_candle: F) {
+        // The remainder of a split trade is evaluated again, as it may complete further candles
+        let mut remainder = self.process_trade(trade, &mut on_candle);
+        while let Some(trade) = remainder {
+            remainder = self.process_trade(&trade, &mut on_candle);
+        }
+    }
+
+    /// Process a single trade according to the decision of the aggregation rule
+    ///
+    /// # Returns:
+    /// The remainder of the trade if it has been split, which still needs to be processed
+    fn process_trade<F: FnMut(C)>(&mut self, trade: &T, on_candle: &mut F) -> Option<T> {
+        let candle = match self.aggregation_rule.should_trigger(trade, &self.candle) {
+            TriggerDecision::Continue => {
+                self.update_candle(trade);
+                None
+            }
+            TriggerDecision::CloseBefore => {
+                let candle = self.take_candle();
+                self.update_candle(trade);
+                candle
+            }
+            // Nothing of the trade belongs to the closing candle,
+            // which includes the case of a split size with the wrong sign
+            TriggerDecision::CloseAndSplit(size) if size / trade.size() <= 0.0 => {
+                let candle = self.take_candle();
+                self.update_candle(trade);
+                candle
+            }
+            // A split size which is not finite, e.g.: for a trade with a size of NaN, can't be used
+            TriggerDecision::CloseAndSplit(size) if !size.is_finite() => {
+                self.close_including(trade)
+            }
+            TriggerDecision::CloseIncluding => self.close_including(trade),
+            TriggerDecision::CloseAndSplit(size) => {
+                // The closing candle includes at most the whole trade
+                let size = if size.abs() > trade.size().abs() {
+                    trade.size()
+                } else {
+                    size
+                };
+                match (trade.with_size(size), trade.with_size(trade.size() - size)) {
+                    (Some(closing), Some(next)) => {
+                        self.update_candle(&closing);
+                        if let Some(candle) = self.take_candle() {
+                            on_candle(candle);
+                        }
+                        // Only a remainder smaller than the trade is evaluated again,
+                        // which ensures that splitting a trade terminates
+                        return (next.size() != 0.0 && next.size().abs() < trade.size().abs())
+                            .then_some(next);
+                    }
+                    _ => self.close_including(trade),
+                }
+            }
+        };
+        if let Some(candle) = candle {
+            on_candle(candle);
+        }
+        None
+    }
+
+    /// Closes the current candle if the aggregation rule decides it is finished at `timestamp`
+    fn close_at(&mut self, timestamp: i64) -> Option<C> {
+        if !self.has_trades {
+            return None;
+        }
+        if self
+            .aggregation_rule
+            .should_trigger_at(timestamp, &self.candle)
+        {
+            return self.take_candle();
+        }
+        None
+    }
+
+    /// Takes the incomplete current candle, such that the next trade starts a new aggregation period
+    fn finish_candle(&mut self) -> Option<C> {
+        let candle = self.take_candle();
+        if candle.is_some() {
+            self.aggregation_rule.reset();
+        }
+        candle
+    }
+
+    /// Closes the current candle, including the trade according to the `BoundaryPolicy`
+    fn close_including(&mut self, trade: &T) -> Option<C> {
+        match self.boundary_policy {
+            BoundaryPolicy::Closing => {
+                self.update_candle(trade);
+                self.take_candle()
+            }
+            BoundaryPolicy::Next => {
+                let candle = self.take_candle();
+                self.update_candle(trade);
+                candle
+            }
+            BoundaryPolicy::Both => {
+                self.update_candle(trade);
+                let candle = self.take_candle();
+                // Also include the initial information in the candle.
+                // This means the trade data at the boundary is included twice.
+                // This especially ensures `Open` and `Close` values are correct.
+                self.update_candle(trade);
+                candle
+            }
         }
     }
 }
@@ -49,21 +304,42 @@ where
     T: TakerTrade,
 {
     fn update(&mut self, trade: &T) -> Option<C> {
-        // Always update the candle with the newest information.
-        self.candle.update(trade);
+        let mut pending = std::mem::take(&mut self.pending);
+        self.process(trade, |candle| pending.push_back(candle));
+        let candle = pending.pop_front();
+        self.pending = pending;
+        candle
+    }
 
-        if self.aggregation_rule.should_trigger(trade, &self.candle) {
-            let candle = self.candle.clone();
-            self.candle.reset();
-            // Also include the initial information in the candle.
-            // This means the trade data at the boundary is included twice.
-            // This especially ensures `Open` and `Close` values are correct.
-            // TODO: If this behaviour of including trade info at the boundary in both candles,
-            // A flag may be added to specify the exact behaviour.
-            self.candle.update(trade);
-            return Some(candle);
-        }
-        None
+    fn update_into(&mut self, trade: &T, candles: &mut Vec<C>) {
+        candles.extend(self.pending.drain(..));
+        self.process(trade, |candle| candles.push(candle));
+    }
+
+    fn advance_time(&mut self, timestamp: i64) -> Option<C> {
+        let candle = self.close_at(timestamp);
+        self.pending.extend(candle);
+        self.pending.pop_front()
+    }
+
+    fn advance_time_into(&mut self, timestamp: i64, candles: &mut Vec<C>) {
+        candles.extend(self.pending.drain(..));
+        candles.extend(self.close_at(timestamp));
+    }
+
+    fn flush_into(&mut self, candles: &mut Vec<C>) {
+        candles.extend(self.pending.drain(..));
+    }
+
+    fn finish(&mut self) -> Option<C> {
+        let candle = self.finish_candle();
+        self.pending.extend(candle);
+        self.pending.pop_front()
+    }
+
+    fn finish_into(&mut self, candles: &mut Vec<C>) {
+        candles.extend(self.pending.drain(..));
+        candles.extend(self.finish_candle());
     }
 }
 
@@ -73,15 +349,23 @@ mod tests {
 
     use super::*;
     use crate::{
-        candle_components::{CandleComponent, CandleComponentUpdate, Close, NumTrades, Open},
-        load_trades_from_csv, ModularCandle, TimeRule, TimestampResolution, Trade, M1,
+        aggregate_all_trades, aggregate_all_trades_with_partial,
+        candle_components::{
+            CandleComponent, CandleComponentUpdate, Close, NumTrades, Open, Volume,
+        },
+        load_trades_from_csv, AlignedTimeRule, AllOf, AnyOf, By, ContractType, DollarImbalanceRule,
+        DollarRule, DollarRunRule, ModularCandle, RelativePriceRule, TickImbalanceRule, TickRule,
+        TickRunRule, TimeRule, TimestampResolution, Trade, VolumeImbalanceRule, VolumeRule,
+        VolumeRunRule, M1,
     };
 
     #[derive(Default, Debug, Clone, Candle)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     struct MyCandle {
         open: Open,
         close: Close,
         num_trades: NumTrades<u32>,
+        volume: Volume,
     }
 
     #[test]
@@ -106,6 +390,361 @@ mod tests {
         assert_eq!(candle_counter, 5704);
     }
 
+    /// Snapshots the aggregator in the middle of a candle, resumes from the snapshot
+    /// and checks that the candles equal the ones of an uninterrupted run
+    #[cfg(feature = "serde")]
+    fn assert_resumes_from_snapshot<R>(trades: &[Trade], rule: R)
+    where
+        R: AggregationRule<MyCandle, Trade>
+            + Clone
+            + serde::Serialize
+            + serde::de::DeserializeOwned,
+    {
+        use crate::aggregate_all_trades;
+
+        let (first, second) = trades.split_at(trades.len() / 2 + 123);
+
+        let mut aggregator = GenericAggregator::<MyCandle, _, Trade>::new(rule.clone());
+        let expected = aggregate_all_trades(trades, &mut aggregator);
+        assert!(expected.len() > 10);
+
+        let mut aggregator = GenericAggregator::<MyCandle, _, Trade>::new(rule);
+        let mut candles = aggregate_all_trades(first, &mut aggregator);
+        let snapshot = serde_json::to_string(&aggregator).unwrap();
+
+        let mut aggregator: GenericAggregator<MyCandle, R, Trade> =
+            serde_json::from_str(&snapshot).unwrap();
+        candles.append(&mut aggregate_all_trades(second, &mut aggregator));
+
+        assert_eq!(candles.len(), expected.len());
+        for (c, e) in candles.iter().zip(expected.iter()) {
+            assert_eq!(c.open(), e.open());
+            assert_eq!(c.close(), e.close());
+            assert_eq!(c.num_trades(), e.num_trades());
+            assert_eq!(c.volume(), e.volume());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn generic_aggregator_serde() {
+        use crate::M5;
+
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv")
+            .expect("Could not load trades from file!");
+        let bounds = (100, 10_000);
+
+        assert_resumes_from_snapshot(
+            &trades,
+            AlignedTimeRule::new(M5, TimestampResolution::Millisecond),
+        );
+
+        // The estimators of the imbalance and run rules carry their state across candles
+        let trades = &trades[..200_000];
+        assert_resumes_from_snapshot(
+            trades,
+            VolumeImbalanceRule::new(By::Quote, 1000, bounds, 10, 10_000, 10).unwrap(),
+        );
+        assert_resumes_from_snapshot(
+            trades,
+            TickRunRule::new(1000, bounds, 10, 10_000, 10).unwrap(),
+        );
+        assert_resumes_from_snapshot(
+            trades,
+            AnyOf::new(
+                TimeRule::new(M1, TimestampResolution::Millisecond),
+                DollarRunRule::new(ContractType::Inverse, 1000, bounds, 10, 10_000, 10).unwrap(),
+            ),
+        );
+    }
+
+    #[test]
+    fn generic_aggregator_advance_time() {
+        let rule = AlignedTimeRule::new(M1, TimestampResolution::Second);
+        let mut a = GenericAggregator::<MyCandle, AlignedTimeRule, Trade>::new(rule);
+
+        let trade = |timestamp, price| Trade {
+            timestamp,
+            price,
+            size: 1.0,
+        };
+        assert!(a.update(&trade(0, 100.0)).is_none());
+        assert!(a.update(&trade(30, 101.0)).is_none());
+        assert!(a.advance_time(59).is_none());
+
+        // The candle closes on schedule without waiting for the next trade
+        let candle = a.advance_time(61).unwrap();
+        assert_eq!(candle.open(), 100.0);
+        assert_eq!(candle.close(), 101.0);
+        assert_eq!(candle.num_trades(), 2);
+
+        // No empty candles are created while there are no trades
+        assert!(a.advance_time(200).is_none());
+
+        // The next trade starts a new period
+        assert!(a.update(&trade(300, 105.0)).is_none());
+        assert!(a.advance_time(359).is_none());
+        let candle = a.advance_time(361).unwrap();
+        assert_eq!(candle.open(), 105.0);
+        assert_eq!(candle.num_trades(), 1);
+    }
+
+    #[test]
+    fn generic_aggregator_finish() {
+        let rule = TimeRule::new(M1, TimestampResolution::Second);
+        let mut a = GenericAggregator::<MyCandle, TimeRule, Trade>::new(rule);
+        assert!(a.finish().is_none());
+
+        for (timestamp, price) in [(0, 100.0), (30, 101.0), (61, 102.0), (90, 103.0)] {
+            a.update(&Trade {
+                timestamp,
+                price,
+                size: 1.0,
+            });
+        }
+        let candle = a.finish().unwrap();
+        assert_eq!(candle.open(), 102.0);
+        assert_eq!(candle.close(), 103.0);
+        assert_eq!(candle.num_trades(), 2);
+        assert!(a.finish().is_none());
+    }
+
+    fn num_trades_per_policy<R>(trades: &[Trade], rule: R, closes_before: bool)
+    where
+        R: AggregationRule<MyCandle, Trade> + Clone,
+    {
+        for policy in [
+            BoundaryPolicy::Closing,
+            BoundaryPolicy::Next,
+            BoundaryPolicy::Both,
+        ] {
+            let mut a = GenericAggregator::with_boundary_policy(rule.clone(), policy);
+            let (candles, partial) = aggregate_all_trades_with_partial(trades, &mut a);
+            assert!(!candles.is_empty());
+            let num_trades: usize = candles
+                .iter()
+                .chain(partial.iter())
+                .map(|c| c.num_trades() as usize)
+                .sum();
+
+            // Only `Both` counts the trade at the boundary twice,
+            // unless the rule decides that it belongs to the next candle
+            let expected = match policy {
+                BoundaryPolicy::Both if !closes_before => trades.len() + candles.len(),
+                _ => trades.len(),
+            };
+            assert_eq!(num_trades, expected, "{policy:?}");
+        }
+    }
+
+    #[test]
+    fn generic_aggregator_boundary_policies() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+        let trades = &trades[..100_000];
+        let bounds = (100, 10_000);
+
+        // The time rules close the candle before the trade at the boundary
+        num_trades_per_policy(
+            trades,
+            TimeRule::new(M1, TimestampResolution::Millisecond),
+            true,
+        );
+        num_trades_per_policy(
+            trades,
+            AlignedTimeRule::new(M1, TimestampResolution::Millisecond),
+            true,
+        );
+
+        num_trades_per_policy(trades, TickRule::new(1000), false);
+        num_trades_per_policy(
+            trades,
+            VolumeRule::new(100_000.0, By::Quote).unwrap(),
+            false,
+        );
+        num_trades_per_policy(
+            trades,
+            DollarRule::new(1.0, ContractType::Inverse).unwrap(),
+            false,
+        );
+        num_trades_per_policy(trades, RelativePriceRule::new(0.001).unwrap(), false);
+        num_trades_per_policy(
+            trades,
+            TickImbalanceRule::new(1000, bounds, 10, 10_000, 10).unwrap(),
+            false,
+        );
+        num_trades_per_policy(
+            trades,
+            VolumeImbalanceRule::new(By::Quote, 1000, bounds, 10, 10_000, 10).unwrap(),
+            false,
+        );
+        num_trades_per_policy(
+            trades,
+            DollarImbalanceRule::new(ContractType::Inverse, 1000, bounds, 10, 10_000, 10).unwrap(),
+            false,
+        );
+        num_trades_per_policy(
+            trades,
+            TickRunRule::new(1000, bounds, 10, 10_000, 10).unwrap(),
+            false,
+        );
+        num_trades_per_policy(
+            trades,
+            VolumeRunRule::new(By::Quote, 1000, bounds, 10, 10_000, 10).unwrap(),
+            false,
+        );
+        num_trades_per_policy(
+            trades,
+            DollarRunRule::new(ContractType::Inverse, 1000, bounds, 10, 10_000, 10).unwrap(),
+            false,
+        );
+        num_trades_per_policy(
+            trades,
+            AnyOf::new(
+                TickRule::new(1000),
+                VolumeRule::new(100_000.0, By::Quote).unwrap(),
+            ),
+            false,
+        );
+        num_trades_per_policy(
+            trades,
+            AllOf::new(
+                TickRule::new(1000),
+                VolumeRule::new(100_000.0, By::Quote).unwrap(),
+            ),
+            false,
+        );
+    }
+
+    #[test]
+    fn generic_aggregator_split() {
+        let rule = VolumeRule::exact(1.0, By::Quote).unwrap();
+        let mut a = GenericAggregator::<MyCandle, _, Trade>::new(rule);
+        let trade = |timestamp, size| Trade {
+            timestamp,
+            price: 100.0,
+            size,
+        };
+
+        assert!(a.update(&trade(0, 0.5)).is_none());
+
+        let candle = a.update(&trade(1, 1.0)).unwrap();
+        assert_eq!(candle.volume(), 1.0);
+        assert_eq!(candle.num_trades(), 2);
+        let candle = a.finish().unwrap();
+        assert_eq!(candle.volume(), 0.5);
+
+        let mut candles = vec![];
+        a.update_into(&trade(2, -2.5), &mut candles);
+        assert_eq!(candles.len(), 2);
+        assert!(candles.iter().all(|c| c.volume() == 1.0));
+        assert_eq!(a.finish().unwrap().volume(), 0.5);
+
+        // `update` returns the first of the candles created by a single trade,
+        // the others are returned by the following calls
+        let candle = a.update(&trade(3, 3.5)).unwrap();
+        assert_eq!(candle.open(), 100.0);
+        assert_eq!(candle.volume(), 1.0);
+        let candle = a.update(&trade(4, 0.25)).unwrap();
+        assert_eq!(candle.volume(), 1.0);
+        assert_eq!(a.update(&trade(5, 0.25)).unwrap().volume(), 1.0);
+        assert_eq!(a.finish().unwrap().volume(), 1.0);
+        assert!(a.update(&trade(6, 0.25)).is_none());
+        assert_eq!(a.finish().unwrap().volume(), 0.25);
+        assert!(a.finish().is_none());
+    }
+
+    /// Splits every trade into the given size and the remainder
+    struct SplitRule(f64);
+
+    impl AggregationRule<MyCandle, Trade> for SplitRule {
+        fn should_trigger(&mut self, _trade: &Trade, _candle: &MyCandle) -> TriggerDecision {
+            TriggerDecision::CloseAndSplit(self.0)
+        }
+    }
+
+    #[test]
+    fn generic_aggregator_split_range() {
+        let trade = |timestamp, size| Trade {
+            timestamp,
+            price: 100.0,
+            size,
+        };
+        let trades = [trade(0, 2.0), trade(1, 3.0)];
+
+        // A split size of zero or with the wrong sign closes the candle before the trade
+        for split in [0.0, -1.0] {
+            let mut a = GenericAggregator::<MyCandle, _, Trade>::new(SplitRule(split));
+            let (candles, partial) = aggregate_all_trades_with_partial(&trades, &mut a);
+            assert_eq!(candles.len(), 1);
+            assert_eq!(candles[0].volume(), 2.0);
+            assert_eq!(partial.unwrap().volume(), 3.0);
+        }
+
+        // A split size exceeding the trade includes the whole trade in the closing candle
+        let mut a = GenericAggregator::<MyCandle, _, Trade>::new(SplitRule(5.0));
+        let (candles, partial) = aggregate_all_trades_with_partial(&trades[..1], &mut a);
+        assert_eq!(candles.len(), 1);
+        assert_eq!(candles[0].volume(), 2.0);
+        assert_eq!(candles[0].num_trades(), 1);
+        assert!(partial.is_none());
+    }
+
+    #[test]
+    fn generic_aggregator_split_non_finite() {
+        let trade = |timestamp, size| Trade {
+            timestamp,
+            price: 100.0,
+            size,
+        };
+
+        // A split size of NaN includes the trade as a whole
+        let mut a = GenericAggregator::<MyCandle, _, Trade>::with_boundary_policy(
+            SplitRule(f64::NAN),
+            BoundaryPolicy::Closing,
+        );
+        let candles = aggregate_all_trades(&[trade(0, 2.0)], &mut a);
+        assert_eq!(candles.len(), 1);
+        assert_eq!(candles[0].volume(), 2.0);
+
+        // The remainder of a trade with a size of NaN is never smaller, so it is not evaluated again
+        let mut a = GenericAggregator::<MyCandle, _, Trade>::new(SplitRule(0.5));
+        let candles = aggregate_all_trades(&[trade(0, f64::NAN)], &mut a);
+        assert_eq!(candles.len(), 1);
+        assert_eq!(candles[0].volume(), 0.5);
+        assert!(a.finish().is_none());
+    }
+
+    #[test]
+    fn generic_aggregator_boundary_policy_open_close() {
+        let trade = |timestamp, price| Trade {
+            timestamp,
+            price,
+            size: 1.0,
+        };
+        let trades = [trade(0, 100.0), trade(1, 101.0), trade(2, 102.0)];
+
+        let mut a = GenericAggregator::<MyCandle, _, Trade>::with_boundary_policy(
+            TickRule::new(2),
+            BoundaryPolicy::Closing,
+        );
+        let (candles, partial) = aggregate_all_trades_with_partial(&trades, &mut a);
+        assert_eq!(candles[0].close(), 101.0);
+        assert_eq!(partial.unwrap().open(), 102.0);
+
+        let mut a = GenericAggregator::<MyCandle, _, Trade>::with_boundary_policy(
+            TickRule::new(2),
+            BoundaryPolicy::Next,
+        );
+        let (candles, partial) = aggregate_all_trades_with_partial(&trades, &mut a);
+        assert_eq!(candles[0].close(), 100.0);
+        assert_eq!(partial.unwrap().open(), 101.0);
+
+        let mut a = GenericAggregator::<MyCandle, _, Trade>::new(TickRule::new(2));
+        let (candles, partial) = aggregate_all_trades_with_partial(&trades, &mut a);
+        assert_eq!(candles[0].close(), 101.0);
+        assert_eq!(partial.unwrap().open(), 101.0);
+    }
+
     #[test]
     fn candle_macro() {
         let my_candle = MyCandle::default();
@@ -1,4 +1,4 @@
-use crate::{CandleComponent, CandleComponentUpdate, TakerTrade};
+use crate::{CandleComponent, CandleComponentMerge, CandleComponentUpdate, TakerTrade};
 
 /// This 'CandleComponent' keeps track of the arithmetic mean price
 #[derive(Debug, Default, Clone)]
@@ -29,6 +29,14 @@ impl<T: TakerTrade> CandleComponentUpdate<T> for AveragePrice {
     }
 }
 
+impl CandleComponentMerge for AveragePrice {
+    #[inline(always)]
+    fn merge(&mut self, other: &Self) {
+        self.num_trades += other.num_trades;
+        self.price_sum += other.price_sum;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -14,4 +14,18 @@ pub trait CandleComponent<T> {
 pub trait CandleComponentUpdate<T: TakerTrade> {
     /// Updates the state with newest trade information
     fn update(&mut self, trade: &T);
+
+    /// Updates the state for a period without any trades, starting at `timestamp`,
+    /// given the price of the last trade, e.g.: for the gaps filled by `GapFillingAggregator`.
+    /// The default leaves the state unchanged, which suits components describing the trades,
+    /// such as `Volume` or `NumTrades`.
+    fn fill_gap(&mut self, _timestamp: i64, _price: f64) {}
+}
+
+/// Optionally implemented by components whose state can be merged,
+/// e.g.: to build hourly candles from minute candles without the original trades
+pub trait CandleComponentMerge {
+    /// Merges the state of `other`, which observed the trades following the ones of `self`,
+    /// such that `self` is in the same state as if it observed the trades of both
+    fn merge(&mut self, other: &Self);
 }
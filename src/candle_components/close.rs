@@ -1,4 +1,4 @@
-use crate::{CandleComponent, CandleComponentUpdate, TakerTrade};
+use crate::{CandleComponent, CandleComponentMerge, CandleComponentUpdate, TakerTrade};
 
 /// This 'CandleComponent' keeps track of the close price
 #[derive(Default, Debug, Clone)]
@@ -22,6 +22,18 @@ impl<T: TakerTrade> CandleComponentUpdate<T> for Close {
     fn update(&mut self, trade: &T) {
         self.value = trade.price()
     }
+
+    #[inline(always)]
+    fn fill_gap(&mut self, _timestamp: i64, price: f64) {
+        self.value = price
+    }
+}
+
+impl CandleComponentMerge for Close {
+    #[inline(always)]
+    fn merge(&mut self, other: &Self) {
+        self.value = other.value;
+    }
 }
 
 #[cfg(test)]
@@ -1,4 +1,4 @@
-use crate::{CandleComponent, CandleComponentUpdate, TakerTrade};
+use crate::{CandleComponent, CandleComponentMerge, CandleComponentUpdate, TakerTrade};
 
 /// This 'CandleComponent' keeps track of the ratio of buys vs total trades
 #[derive(Debug, Default, Clone)]
@@ -31,6 +31,14 @@ impl<T: TakerTrade> CandleComponentUpdate<T> for DirectionalTradeRatio {
     }
 }
 
+impl CandleComponentMerge for DirectionalTradeRatio {
+    #[inline(always)]
+    fn merge(&mut self, other: &Self) {
+        self.num_buys += other.num_buys;
+        self.num_trades += other.num_trades;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,4 +1,4 @@
-use crate::{CandleComponent, CandleComponentUpdate, TakerTrade};
+use crate::{CandleComponent, CandleComponentMerge, CandleComponentUpdate, TakerTrade};
 
 /// This 'CandleComponent' keeps track of the low price
 #[derive(Debug, Clone)]
@@ -34,6 +34,20 @@ impl<T: TakerTrade> CandleComponentUpdate<T> for Low {
             self.low = trade.price();
         }
     }
+
+    #[inline(always)]
+    fn fill_gap(&mut self, _timestamp: i64, price: f64) {
+        if price < self.low {
+            self.low = price;
+        }
+    }
+}
+
+impl CandleComponentMerge for Low {
+    #[inline(always)]
+    fn merge(&mut self, other: &Self) {
+        self.low = self.low.min(other.low);
+    }
 }
 
 #[cfg(test)]
@@ -24,7 +24,7 @@ mod volume;
 mod weighted_price;
 
 pub use average_price::AveragePrice;
-pub use candle_component_trait::{CandleComponent, CandleComponentUpdate};
+pub use candle_component_trait::{CandleComponent, CandleComponentMerge, CandleComponentUpdate};
 pub use close::Close;
 pub use close_timestamp::CloseTimeStamp;
 pub use directional_trade_ratio::DirectionalTradeRatio;
@@ -51,52 +51,52 @@ mod tests {
 
     pub const TRADES: [Trade; 10] = [
         Trade {
-            timestamp: 1684677200_000,
+            timestamp: 1_684_677_200_000,
             price: 100.0,
             size: 10.0,
         },
         Trade {
-            timestamp: 1684677210_000,
+            timestamp: 1_684_677_210_000,
             price: 101.0,
             size: -10.0,
         },
         Trade {
-            timestamp: 1684677220_000,
+            timestamp: 1_684_677_220_000,
             price: 100.0,
             size: 20.0,
         },
         Trade {
-            timestamp: 1684677230_000,
+            timestamp: 1_684_677_230_000,
             price: 102.0,
             size: 10.0,
         },
         Trade {
-            timestamp: 1684677240_000,
+            timestamp: 1_684_677_240_000,
             price: 103.0,
             size: 10.0,
         },
         Trade {
-            timestamp: 1684677250_000,
+            timestamp: 1_684_677_250_000,
             price: 104.0,
             size: -20.0,
         },
         Trade {
-            timestamp: 1684677260_000,
+            timestamp: 1_684_677_260_000,
             price: 102.0,
             size: -10.0,
         },
         Trade {
-            timestamp: 1684677270_000,
+            timestamp: 1_684_677_270_000,
             price: 101.0,
             size: 10.0,
         },
         Trade {
-            timestamp: 1684677280_000,
+            timestamp: 1_684_677_280_000,
             price: 102.0,
             size: 30.0,
         },
         Trade {
-            timestamp: 1684677290_000,
+            timestamp: 1_684_677_290_000,
             price: 105.0,
             size: 10.0,
         },
@@ -1,4 +1,4 @@
-use crate::{CandleComponent, CandleComponentUpdate, TakerTrade};
+use crate::{CandleComponent, CandleComponentMerge, CandleComponentUpdate, TakerTrade};
 
 /// This 'CandleComponent' keeps track of the number of trades
 #[derive(Debug, Default, Clone)]
@@ -25,6 +25,13 @@ impl<T: TakerTrade> CandleComponentUpdate<T> for NumTrades<u32> {
     }
 }
 
+impl CandleComponentMerge for NumTrades<u32> {
+    #[inline(always)]
+    fn merge(&mut self, other: &Self) {
+        self.value += other.value;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
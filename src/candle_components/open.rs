@@ -1,4 +1,4 @@
-use crate::{CandleComponent, CandleComponentUpdate, TakerTrade};
+use crate::{CandleComponent, CandleComponentMerge, CandleComponentUpdate, TakerTrade};
 
 /// This 'CandleComponent' keeps track of the opening price of a Candle
 #[derive(Debug, Clone)]
@@ -39,6 +39,25 @@ impl<T: TakerTrade> CandleComponentUpdate<T> for Open {
             self.init = false;
         }
     }
+
+    /// Opens at the price of the last trade
+    #[inline(always)]
+    fn fill_gap(&mut self, _timestamp: i64, price: f64) {
+        if self.init {
+            self.value = price;
+            self.init = false;
+        }
+    }
+}
+
+impl CandleComponentMerge for Open {
+    /// Keeps the open price, unless this candle has not observed any trades
+    #[inline(always)]
+    fn merge(&mut self, other: &Self) {
+        if self.init {
+            *self = other.clone();
+        }
+    }
 }
 
 #[cfg(test)]
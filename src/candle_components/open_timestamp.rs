@@ -1,4 +1,4 @@
-use crate::{CandleComponent, CandleComponentUpdate, TakerTrade};
+use crate::{CandleComponent, CandleComponentMerge, CandleComponentUpdate, TakerTrade};
 
 /// This 'CandleComponent' keeps track of the opening timestamp of a Candle, using the
 /// same unit resolution as the underlying input of [`TakerTrade.timestamp()`].
@@ -38,6 +38,25 @@ impl<T: TakerTrade> CandleComponentUpdate<T> for OpenTimeStamp<i64> {
             self.init = false;
         }
     }
+
+    /// Opens at the start of the period
+    #[inline(always)]
+    fn fill_gap(&mut self, timestamp: i64, _price: f64) {
+        if self.init {
+            self.value = timestamp;
+            self.init = false;
+        }
+    }
+}
+
+impl CandleComponentMerge for OpenTimeStamp<i64> {
+    /// Keeps the opening timestamp, unless this candle has not observed any trades
+    #[inline(always)]
+    fn merge(&mut self, other: &Self) {
+        if self.init {
+            *self = other.clone();
+        }
+    }
 }
 
 #[cfg(test)]
@@ -1,4 +1,7 @@
-use crate::{welford_online::WelfordOnline, CandleComponent, CandleComponentUpdate, TakerTrade};
+use crate::{
+    welford_online::WelfordOnline, CandleComponent, CandleComponentMerge, CandleComponentUpdate,
+    TakerTrade,
+};
 
 /// This 'CandleComponent' keeps track of the standard deviation in trade prices
 #[derive(Debug, Clone)]
@@ -33,3 +36,10 @@ impl<T: TakerTrade> CandleComponentUpdate<T> for StdDevPrices {
         self.welford.add(trade.price());
     }
 }
+
+impl CandleComponentMerge for StdDevPrices {
+    #[inline(always)]
+    fn merge(&mut self, other: &Self) {
+        self.welford.merge(&other.welford);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{CandleComponent, CandleComponentUpdate, TakerTrade};
+use crate::{CandleComponent, CandleComponentMerge, CandleComponentUpdate, TakerTrade};
 
 /// This 'CandleComponent' keeps track of the cumulative volume
 #[derive(Debug, Default, Clone)]
@@ -26,6 +26,13 @@ impl<T: TakerTrade> CandleComponentUpdate<T> for Volume {
     }
 }
 
+impl CandleComponentMerge for Volume {
+    #[inline(always)]
+    fn merge(&mut self, other: &Self) {
+        self.volume += other.volume;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
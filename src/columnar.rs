@@ -0,0 +1,569 @@
+use std::{fmt, fs::File, sync::Arc};
+
+use arrow_array::{
+    builder::{Float64Builder, Int64Builder, UInt64Builder},
+    cast::AsArray,
+    types::{Float64Type, Int64Type},
+    ArrayRef, RecordBatch, RecordBatchReader,
+};
+use arrow_cast::cast;
+use arrow_schema::{DataType, Field, Schema};
+
+use crate::{CandleColumns, ColumnValue, Error, Result, SideEncoding, Trade};
+
+/// The names of the columns containing the timestamp, price and size of the trades in columnar data,
+/// such as Parquet or Arrow IPC files.
+/// The columns may be of any numeric type, including arrow timestamps,
+/// whose values are read as they are, in the unit of the column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TradeColumns {
+    /// The name of the column containing the timestamps
+    pub timestamp: String,
+
+    /// The name of the column containing the prices
+    pub price: String,
+
+    /// The name of the column containing the sizes,
+    /// which are signed unless there is a side column
+    pub size: String,
+
+    /// The name of the column denoting the side of the taker, if any,
+    /// which determines the sign of the size, see `TradeColumns::with_side_column`
+    pub side: Option<String>,
+
+    /// How the side column denotes the side of the taker
+    pub side_encoding: SideEncoding,
+}
+
+impl Default for TradeColumns {
+    fn default() -> Self {
+        Self::new("timestamp", "price", "size")
+    }
+}
+
+impl TradeColumns {
+    /// Create a new instance with the given column names
+    pub fn new(timestamp: &str, price: &str, size: &str) -> Self {
+        Self {
+            timestamp: timestamp.to_string(),
+            price: price.to_string(),
+            size: size.to_string(),
+            side: None,
+            side_encoding: SideEncoding::Taker,
+        }
+    }
+
+    /// Sets the column denoting the side of the taker, e.g.: `buy` or `sell`,
+    /// which determines the sign of the size, as sizes are unsigned in that case.
+    /// The column may be of a string or boolean type, e.g.: `is_buyer_maker`,
+    /// see `with_side_encoding` for the values being accepted.
+    pub fn with_side_column(mut self, side: &str) -> Self {
+        self.side = Some(side.to_string());
+        self
+    }
+
+    /// Sets how the side column denotes the side of the taker
+    pub fn with_side_encoding(mut self, side_encoding: SideEncoding) -> Self {
+        self.side_encoding = side_encoding;
+        self
+    }
+
+    /// The names of all columns being read
+    #[cfg(feature = "parquet")]
+    fn names(&self) -> impl Iterator<Item = &str> {
+        [&self.timestamp, &self.price, &self.size]
+            .into_iter()
+            .chain(self.side.as_ref())
+            .map(String::as_str)
+    }
+}
+
+/// Convert a batch of columnar data into trades
+///
+/// # Arguments:
+/// batch: The columnar data, containing at least the columns named by `columns`
+/// columns: The names of the columns containing the trade information
+///
+/// # Returns
+/// If Ok, A vector of the trades inside the batch
+pub fn trades_from_record_batch(batch: &RecordBatch, columns: &TradeColumns) -> Result<Vec<Trade>> {
+    let column = |name: &str, data_type: &DataType| -> Result<ArrayRef> {
+        let array = batch
+            .column_by_name(name)
+            .ok_or_else(|| Error::MissingColumn(name.to_string()))?;
+        // Values which can't be converted become null as well
+        let array = cast(array, data_type)?;
+        if array.null_count() > 0 {
+            return Err(Error::NullValues(name.to_string()));
+        }
+        Ok(array)
+    };
+    let timestamps = column(&columns.timestamp, &DataType::Int64)?;
+    let prices = column(&columns.price, &DataType::Float64)?;
+    let sizes = column(&columns.size, &DataType::Float64)?;
+    let sides = columns
+        .side
+        .as_ref()
+        .map(|side| column(side, &DataType::Utf8))
+        .transpose()?;
+
+    let timestamps = timestamps.as_primitive::<Int64Type>().values();
+    let prices = prices.as_primitive::<Float64Type>().values();
+    let sizes = sizes.as_primitive::<Float64Type>().values();
+    let sides = sides.as_ref().map(|sides| sides.as_string::<i32>());
+
+    let mut out = Vec::with_capacity(batch.num_rows());
+    for (i, ((&timestamp, &price), &size)) in timestamps
+        .iter()
+        .zip(prices.iter())
+        .zip(sizes.iter())
+        .enumerate()
+    {
+        let size = match sides {
+            Some(sides) => columns.side_encoding.signed_size(sides.value(i), size)?,
+            None => size,
+        };
+        out.push(Trade {
+            timestamp,
+            price,
+            size,
+        });
+    }
+
+    Ok(out)
+}
+
+/// Reads trades from columnar data one batch at a time, instead of loading all of them into memory,
+/// e.g.: from an Arrow IPC or Parquet file.
+/// Combine it with `AggregateExt` to aggregate files that don't fit into memory, as with a `TradeCsvReader`.
+pub struct ColumnarTradeReader<R> {
+    reader: R,
+    columns: TradeColumns,
+
+    // The trades of the current batch which have not been yielded yet
+    trades: std::vec::IntoIter<Trade>,
+}
+
+impl<R> fmt::Debug for ColumnarTradeReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ColumnarTradeReader")
+            .field("columns", &self.columns)
+            .field("num_buffered_trades", &self.trades.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<R: RecordBatchReader> ColumnarTradeReader<R> {
+    /// Create a new instance reading the batches of `reader`
+    ///
+    /// # Arguments:
+    /// reader: The source of the batches of columnar data
+    /// columns: The names of the columns containing the trade information
+    ///
+    pub fn new(reader: R, columns: TradeColumns) -> Self {
+        Self {
+            reader,
+            columns,
+            trades: vec![].into_iter(),
+        }
+    }
+}
+
+impl ColumnarTradeReader<arrow_ipc::reader::FileReader<File>> {
+    /// Create a new instance reading the Arrow IPC file at the given path
+    pub fn from_arrow_ipc(filename: &str, columns: TradeColumns) -> Result<Self> {
+        let reader = arrow_ipc::reader::FileReader::try_new(File::open(filename)?, None)?;
+        Ok(Self::new(reader, columns))
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl ColumnarTradeReader<parquet::arrow::arrow_reader::ParquetRecordBatchReader> {
+    /// Create a new instance reading the Parquet file at the given path,
+    /// only reading the columns containing the trade information
+    pub fn from_parquet(filename: &str, columns: TradeColumns) -> Result<Self> {
+        use parquet::arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ProjectionMask};
+
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(filename)?)?;
+        let projection = ProjectionMask::columns(builder.parquet_schema(), columns.names());
+        let reader = builder.with_projection(projection).build()?;
+        Ok(Self::new(reader, columns))
+    }
+}
+
+impl<R: RecordBatchReader> Iterator for ColumnarTradeReader<R> {
+    type Item = Result<Trade>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(trade) = self.trades.next() {
+                return Some(Ok(trade));
+            }
+            let trades = match self.reader.next()? {
+                Ok(batch) => trades_from_record_batch(&batch, &self.columns),
+                Err(e) => Err(e.into()),
+            };
+            match trades {
+                Ok(trades) => self.trades = trades.into_iter(),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Load trades from an Arrow IPC file
+///
+/// # Arguments:
+/// filename: The path to the Arrow IPC file
+/// columns: The names of the columns containing the trade information
+///
+/// # Returns
+/// If Ok, A vector of the trades inside the file
+pub fn load_trades_from_arrow_ipc(filename: &str, columns: &TradeColumns) -> Result<Vec<Trade>> {
+    ColumnarTradeReader::from_arrow_ipc(filename, columns.clone())?.collect()
+}
+
+/// Load trades from a Parquet file, only reading the columns containing the trade information
+///
+/// # Arguments:
+/// filename: The path to the Parquet file
+/// columns: The names of the columns containing the trade information
+///
+/// # Returns
+/// If Ok, A vector of the trades inside the file
+#[cfg(feature = "parquet")]
+pub fn load_trades_from_parquet(filename: &str, columns: &TradeColumns) -> Result<Vec<Trade>> {
+    ColumnarTradeReader::from_parquet(filename, columns.clone())?.collect()
+}
+
+/// The builder of a single column of candles
+#[derive(Debug)]
+enum ColumnBuilder {
+    Float(Float64Builder),
+    Int(Int64Builder),
+    UInt(UInt64Builder),
+}
+
+impl ColumnBuilder {
+    fn new(value: &ColumnValue, capacity: usize) -> Self {
+        match value {
+            ColumnValue::Float(_) => ColumnBuilder::Float(Float64Builder::with_capacity(capacity)),
+            ColumnValue::Int(_) => ColumnBuilder::Int(Int64Builder::with_capacity(capacity)),
+            ColumnValue::UInt(_) => ColumnBuilder::UInt(UInt64Builder::with_capacity(capacity)),
+        }
+    }
+
+    fn data_type(&self) -> DataType {
+        match self {
+            ColumnBuilder::Float(_) => DataType::Float64,
+            ColumnBuilder::Int(_) => DataType::Int64,
+            ColumnBuilder::UInt(_) => DataType::UInt64,
+        }
+    }
+
+    /// Appends the value to the column
+    ///
+    /// # Returns:
+    /// Whether the value is of the type of the column
+    fn append(&mut self, value: ColumnValue) -> bool {
+        match (self, value) {
+            (ColumnBuilder::Float(builder), ColumnValue::Float(v)) => builder.append_value(v),
+            (ColumnBuilder::Int(builder), ColumnValue::Int(v)) => builder.append_value(v),
+            (ColumnBuilder::UInt(builder), ColumnValue::UInt(v)) => builder.append_value(v),
+            _ => return false,
+        }
+        true
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            ColumnBuilder::Float(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Int(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::UInt(builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+/// Convert candles into a batch of columnar data, with one column per candle component,
+/// named after the field of the candle, see `CandleColumns`
+///
+/// # Arguments:
+/// candles: The candles to convert
+///
+/// # Returns
+/// If Ok, the batch containing one row per candle
+pub fn candles_to_record_batch<C>(candles: &[C]) -> Result<RecordBatch>
+where
+    C: CandleColumns + Default,
+{
+    // The default candle determines the types of the columns even if there are no candles
+    let mut builders: Vec<ColumnBuilder> = Vec::with_capacity(C::column_names().len());
+    C::default()
+        .write_columns(&mut |_, value| builders.push(ColumnBuilder::new(&value, candles.len())));
+
+    // The columns of a candle type never change their type
+    let mut valid = true;
+    for candle in candles {
+        candle.write_columns(&mut |column, value| valid &= builders[column].append(value));
+    }
+    if !valid {
+        return Err(Error::InvalidParam);
+    }
+
+    let fields: Vec<Field> = C::column_names()
+        .iter()
+        .zip(builders.iter())
+        .map(|(name, builder)| Field::new(*name, builder.data_type(), false))
+        .collect();
+    let arrays: Vec<ArrayRef> = builders.iter_mut().map(ColumnBuilder::finish).collect();
+
+    Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)?)
+}
+
+/// Write candles as Parquet, with one column per candle component,
+/// named after the field of the candle, see `CandleColumns`
+///
+/// # Arguments:
+/// candles: The candles to write
+/// writer: The destination, e.g.: a `File`
+///
+#[cfg(feature = "parquet")]
+pub fn write_candles_to_parquet<C, W>(candles: &[C], writer: W) -> Result<()>
+where
+    C: CandleColumns + Default,
+    W: std::io::Write + Send,
+{
+    let batch = candles_to_record_batch(candles)?;
+    let mut writer = parquet::arrow::ArrowWriter::try_new(writer, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{
+        BooleanArray, Float64Array, Int64Array, RecordBatchIterator, StringArray,
+        TimestampMillisecondArray,
+    };
+    use trade_aggregation_derive::Candle;
+
+    use super::*;
+    use crate::{
+        aggregate_all_trades,
+        candle_components::{
+            CandleComponent, CandleComponentUpdate, Close, NumTrades, Open, OpenTimeStamp, Volume,
+        },
+        load_trades_from_csv, GenericAggregator, ModularCandle, TickRule,
+    };
+
+    #[derive(Debug, Default, Clone, Candle)]
+    #[candle(columns)]
+    struct MyCandle {
+        open_timestamp: OpenTimeStamp<i64>,
+        open: Open,
+        close: Close,
+        volume: Volume,
+        num_trades: NumTrades<u32>,
+    }
+
+    /// The trades as columnar data, with a timestamp column of an arrow timestamp type
+    fn trades_to_record_batch(trades: &[Trade]) -> RecordBatch {
+        let timestamps: Vec<i64> = trades.iter().map(|t| t.timestamp).collect();
+        let prices: Vec<f64> = trades.iter().map(|t| t.price).collect();
+        let sizes: Vec<f64> = trades.iter().map(|t| t.size).collect();
+        RecordBatch::try_from_iter([
+            (
+                "ts",
+                Arc::new(TimestampMillisecondArray::from(timestamps)) as ArrayRef,
+            ),
+            ("px", Arc::new(Float64Array::from(prices)) as ArrayRef),
+            ("qty", Arc::new(Float64Array::from(sizes)) as ArrayRef),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn columnar_trades() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+        let trades = &trades[..10_000];
+        let batch = trades_to_record_batch(trades);
+
+        let columns = TradeColumns::new("ts", "px", "qty");
+        assert_eq!(trades_from_record_batch(&batch, &columns).unwrap(), trades);
+        assert!(trades_from_record_batch(&batch, &TradeColumns::default()).is_err());
+
+        let filename = std::env::temp_dir().join("trade_aggregation_trades.arrow");
+        let mut writer = arrow_ipc::writer::FileWriter::try_new(
+            File::create(&filename).unwrap(),
+            &batch.schema(),
+        )
+        .unwrap();
+        writer.write(&batch).unwrap();
+        writer.finish().unwrap();
+        let loaded = load_trades_from_arrow_ipc(filename.to_str().unwrap(), &columns).unwrap();
+        assert_eq!(loaded, trades);
+    }
+
+    #[test]
+    fn columnar_trade_reader() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+        let trades = &trades[..10_000];
+        let batches: Vec<RecordBatch> = trades.chunks(3_000).map(trades_to_record_batch).collect();
+        let schema = batches[0].schema();
+
+        let reader = RecordBatchIterator::new(batches.into_iter().map(Ok), schema);
+        let columns = TradeColumns::new("ts", "px", "qty");
+        let read: Vec<Trade> = ColumnarTradeReader::new(reader, columns)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(read, trades);
+    }
+
+    #[test]
+    fn columnar_trades_side_column() {
+        let batch = RecordBatch::try_from_iter([
+            ("ts", Arc::new(Int64Array::from(vec![1, 2, 3])) as ArrayRef),
+            (
+                "px",
+                Arc::new(Float64Array::from(vec![100.0, 101.0, 102.0])) as ArrayRef,
+            ),
+            (
+                "qty",
+                Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0])) as ArrayRef,
+            ),
+            (
+                "side",
+                Arc::new(StringArray::from(vec!["buy", "Sell", "bid"])) as ArrayRef,
+            ),
+            (
+                "is_buyer_maker",
+                Arc::new(BooleanArray::from(vec![true, false, true])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let sizes = |columns: &TradeColumns| -> Vec<f64> {
+            trades_from_record_batch(&batch, columns)
+                .unwrap()
+                .iter()
+                .map(|t| t.size)
+                .collect()
+        };
+
+        let columns = TradeColumns::new("ts", "px", "qty").with_side_column("side");
+        assert_eq!(sizes(&columns), vec![1.0, -2.0, -3.0]);
+
+        let columns = TradeColumns::new("ts", "px", "qty")
+            .with_side_column("is_buyer_maker")
+            .with_side_encoding(SideEncoding::BuyerIsMaker);
+        assert_eq!(sizes(&columns), vec![-1.0, 2.0, -3.0]);
+
+        let columns = TradeColumns::new("ts", "px", "qty").with_side_column("is_buyer_maker");
+        assert!(trades_from_record_batch(&batch, &columns).is_err());
+    }
+
+    #[test]
+    fn columnar_trades_null_values() {
+        // Prices which can't be converted to numbers become null when casting
+        let batch = RecordBatch::try_from_iter([
+            ("ts", Arc::new(Int64Array::from(vec![1, 2])) as ArrayRef),
+            (
+                "px",
+                Arc::new(StringArray::from(vec!["100.5", "n/a"])) as ArrayRef,
+            ),
+            (
+                "qty",
+                Arc::new(Float64Array::from(vec![1.0, 2.0])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let columns = TradeColumns::new("ts", "px", "qty");
+        assert!(matches!(
+            trades_from_record_batch(&batch, &columns),
+            Err(Error::NullValues(_))
+        ));
+
+        let batch = batch.slice(0, 1);
+        assert_eq!(
+            trades_from_record_batch(&batch, &columns).unwrap(),
+            vec![Trade {
+                timestamp: 1,
+                price: 100.5,
+                size: 1.0
+            }]
+        );
+    }
+
+    #[test]
+    fn columnar_candles() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+        let mut aggregator = GenericAggregator::<MyCandle, _, Trade>::new(TickRule::new(1000));
+        let candles = aggregate_all_trades(&trades, &mut aggregator);
+
+        let batch = candles_to_record_batch(&candles).unwrap();
+        assert_eq!(batch.num_rows(), candles.len());
+        let schema = batch.schema();
+        let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["open_timestamp", "open", "close", "volume", "num_trades"]
+        );
+        assert_eq!(names, MyCandle::column_names());
+        assert_eq!(schema.field(0).data_type(), &DataType::Int64);
+        assert_eq!(schema.field(4).data_type(), &DataType::UInt64);
+        let closes = batch.column(2).as_primitive::<Float64Type>();
+        assert_eq!(closes.value(7), candles[7].close());
+
+        assert_eq!(
+            candles_to_record_batch::<MyCandle>(&[])
+                .unwrap()
+                .num_columns(),
+            5
+        );
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_round_trip() {
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+        let trades = &trades[..10_000];
+
+        // Trades
+        let batch = trades_to_record_batch(trades);
+        let filename = std::env::temp_dir().join("trade_aggregation_trades.parquet");
+        let mut writer = parquet::arrow::ArrowWriter::try_new(
+            File::create(&filename).unwrap(),
+            batch.schema(),
+            None,
+        )
+        .unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        let columns = TradeColumns::new("ts", "px", "qty");
+        let loaded = load_trades_from_parquet(filename.to_str().unwrap(), &columns).unwrap();
+        assert_eq!(loaded, trades);
+
+        // Candles
+        let mut aggregator = GenericAggregator::<MyCandle, _, Trade>::new(TickRule::new(100));
+        let candles = aggregate_all_trades(trades, &mut aggregator);
+        let filename = std::env::temp_dir().join("trade_aggregation_candles.parquet");
+        write_candles_to_parquet(&candles, File::create(&filename).unwrap()).unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&filename).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<RecordBatch> = reader.map(|batch| batch.unwrap()).collect();
+        let num_rows: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+        assert_eq!(num_rows, candles.len());
+        let volumes = batches[0]
+            .column_by_name("volume")
+            .unwrap()
+            .as_primitive::<Float64Type>();
+        assert_eq!(volumes.value(0), candles[0].volume());
+    }
+}
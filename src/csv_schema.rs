@@ -0,0 +1,479 @@
+use crate::{Error, Result, TimestampResolution};
+
+/// Identifies a column of csv data, either by its index or by its name in the header row
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CsvColumn {
+    /// The zero based index of the column
+    Index(usize),
+
+    /// The name of the column in the header row
+    Name(String),
+}
+
+impl From<usize> for CsvColumn {
+    fn from(index: usize) -> Self {
+        CsvColumn::Index(index)
+    }
+}
+
+impl From<&str> for CsvColumn {
+    fn from(name: &str) -> Self {
+        CsvColumn::Name(name.to_string())
+    }
+}
+
+impl CsvColumn {
+    /// The index of the column, looking up its name in the header row if needed
+    pub(crate) fn resolve(&self, headers: Option<&csv::StringRecord>) -> Result<usize> {
+        match self {
+            CsvColumn::Index(index) => Ok(*index),
+            CsvColumn::Name(name) => headers
+                .and_then(|headers| headers.iter().position(|header| header == name))
+                .ok_or_else(|| Error::MissingColumn(name.clone())),
+        }
+    }
+}
+
+/// How the timestamps of the trades are denoted in csv data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TimestampFormat {
+    /// An integer, in the `TimestampResolution` of the schema
+    Integer,
+
+    /// A decimal number of seconds since the unix epoch, e.g.: `1514764802.61`
+    FloatSeconds,
+
+    /// An integer whose resolution is inferred from its magnitude, assuming a date between 1973 and 5138,
+    /// e.g.: as Binance spot dumps switched from milliseconds to microseconds in 2025.
+    /// Seconds, milliseconds, microseconds and nanoseconds are told apart, regardless of the `TimestampResolution` of the schema.
+    InferredInteger,
+
+    /// An ISO-8601 date and time, e.g.: `2018-01-01T00:00:02.610Z`.
+    /// The date and time may also be separated by a space or a `D`, as in Bitmex dumps,
+    /// and a missing UTC offset is assumed to be UTC.
+    Iso8601,
+}
+
+/// How the side column of csv data denotes the side of the taker, see `CsvSchema::with_side_column`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SideEncoding {
+    /// The side of the taker, e.g.: `buy` or `sell`.
+    /// The values `buy`, `b`, `sell` and `s` are accepted, ignoring case,
+    /// as well as `bid` and `ask`, denoting the side of the book the trade happened at,
+    /// so `bid` denotes a taker sell with any encoding.
+    #[default]
+    Taker,
+
+    /// The side of the maker, e.g.: `buy` or `sell`, which is the opposite of the side of the taker.
+    /// The same values as for `SideEncoding::Taker` are accepted, with `bid` and `ask` denoting the same side.
+    Maker,
+
+    /// Whether the buyer is the maker, e.g.: `true` or `false`,
+    /// so `true` denotes a taker sell.
+    /// The values `true`, `t`, `1`, `false`, `f` and `0` are accepted, ignoring case.
+    BuyerIsMaker,
+}
+
+impl SideEncoding {
+    /// Applies the side of the taker, as denoted by `side`, to the unsigned size
+    pub(crate) fn signed_size(&self, side: &str, size: f64) -> Result<f64> {
+        let side_lower = side.to_ascii_lowercase();
+        let taker_buys = match (self, side_lower.as_str()) {
+            // A trade at the bid is a taker sell, whichever side is denoted
+            (SideEncoding::Taker | SideEncoding::Maker, "bid") => false,
+            (SideEncoding::Taker | SideEncoding::Maker, "ask") => true,
+            (SideEncoding::Taker, "buy" | "b") => true,
+            (SideEncoding::Taker, "sell" | "s") => false,
+            (SideEncoding::Maker, "buy" | "b") => false,
+            (SideEncoding::Maker, "sell" | "s") => true,
+            (SideEncoding::BuyerIsMaker, "true" | "t" | "1") => false,
+            (SideEncoding::BuyerIsMaker, "false" | "f" | "0") => true,
+            _ => return Err(Error::InvalidSide(side.to_string())),
+        };
+        Ok(if taker_buys { size.abs() } else { -size.abs() })
+    }
+}
+
+/// Describes the layout of csv data containing trades, see `TradeCsvReader::with_schema`.
+/// The default matches the layout read by `load_trades_from_csv`:
+/// a header row, followed by comma separated integer millisecond timestamps, prices and signed sizes.
+/// As the timestamps of a `Trade` are in milliseconds, all timestamps are converted to milliseconds.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CsvSchema {
+    pub(crate) timestamp: CsvColumn,
+    pub(crate) price: CsvColumn,
+    pub(crate) size: CsvColumn,
+    pub(crate) side: Option<CsvColumn>,
+    pub(crate) side_encoding: SideEncoding,
+    pub(crate) trade_id: Option<CsvColumn>,
+    pub(crate) delimiter: u8,
+    pub(crate) has_headers: bool,
+    pub(crate) timestamp_format: TimestampFormat,
+    pub(crate) timestamp_resolution: TimestampResolution,
+}
+
+impl Default for CsvSchema {
+    fn default() -> Self {
+        Self {
+            timestamp: CsvColumn::Index(0),
+            price: CsvColumn::Index(1),
+            size: CsvColumn::Index(2),
+            side: None,
+            side_encoding: SideEncoding::Taker,
+            trade_id: None,
+            delimiter: b',',
+            has_headers: true,
+            timestamp_format: TimestampFormat::Integer,
+            timestamp_resolution: TimestampResolution::Millisecond,
+        }
+    }
+}
+
+impl CsvSchema {
+    /// Create a new instance with the default layout
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the columns of the timestamp, price and size of the trades,
+    /// e.g.: `with_columns("time", "price", "qty")` or `with_columns(0, 1, 2)`
+    pub fn with_columns(
+        mut self,
+        timestamp: impl Into<CsvColumn>,
+        price: impl Into<CsvColumn>,
+        size: impl Into<CsvColumn>,
+    ) -> Self {
+        self.timestamp = timestamp.into();
+        self.price = price.into();
+        self.size = size.into();
+        self
+    }
+
+    /// Sets the column denoting the side of the taker, e.g.: `buy` or `sell`,
+    /// which determines the sign of the size, as sizes are unsigned in that case.
+    /// See `with_side_encoding` for the values being accepted.
+    pub fn with_side_column(mut self, side: impl Into<CsvColumn>) -> Self {
+        self.side = Some(side.into());
+        self
+    }
+
+    /// Sets how the side column denotes the side of the taker
+    pub fn with_side_encoding(mut self, side_encoding: SideEncoding) -> Self {
+        self.side_encoding = side_encoding;
+        self
+    }
+
+    /// Sets the column containing the unique integer id of each trade assigned by the exchange,
+    /// read by `TradeCsvReader::with_trade_ids`, see `TakerTrade::trade_id`
+    pub fn with_trade_id_column(mut self, trade_id: impl Into<CsvColumn>) -> Self {
+        self.trade_id = Some(trade_id.into());
+        self
+    }
+
+    /// Sets the character separating the fields, e.g.: `b';'` or `b'\t'`
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Sets whether the first row contains the names of the columns, instead of a trade
+    pub fn with_headers(mut self, has_headers: bool) -> Self {
+        self.has_headers = has_headers;
+        self
+    }
+
+    /// Sets how the timestamps are denoted
+    pub fn with_timestamp_format(mut self, timestamp_format: TimestampFormat) -> Self {
+        self.timestamp_format = timestamp_format;
+        self
+    }
+
+    /// Sets the resolution of `TimestampFormat::Integer` timestamps, e.g.: microseconds.
+    /// They are converted to the milliseconds of a `Trade`, truncating any finer resolution.
+    pub fn with_timestamp_resolution(mut self, timestamp_resolution: TimestampResolution) -> Self {
+        self.timestamp_resolution = timestamp_resolution;
+        self
+    }
+
+    /// Parse a timestamp according to the format and resolution of the schema
+    ///
+    /// # Returns:
+    /// The timestamp in milliseconds
+    pub(crate) fn parse_timestamp(&self, field: &str) -> Result<i64> {
+        let invalid = || Error::InvalidTimestamp(field.to_string());
+        match self.timestamp_format {
+            TimestampFormat::Integer => {
+                to_milliseconds(field.parse::<i64>()?, self.timestamp_resolution)
+                    .ok_or_else(invalid)
+            }
+            TimestampFormat::InferredInteger => {
+                let timestamp = field.parse::<i64>()?;
+                let resolution = match timestamp.unsigned_abs() {
+                    0..=99_999_999_999 => TimestampResolution::Second,
+                    100_000_000_000..=99_999_999_999_999 => TimestampResolution::Millisecond,
+                    100_000_000_000_000..=99_999_999_999_999_999 => {
+                        TimestampResolution::Microsecond
+                    }
+                    _ => TimestampResolution::Nanosecond,
+                };
+                to_milliseconds(timestamp, resolution).ok_or_else(invalid)
+            }
+            TimestampFormat::FloatSeconds => {
+                let milliseconds = (field.parse::<f64>()? * 1_000.0).round();
+                // Also rejects NaN and the infinities, which would otherwise saturate silently
+                if !(milliseconds >= i64::MIN as f64 && milliseconds < i64::MAX as f64) {
+                    return Err(invalid());
+                }
+                Ok(milliseconds as i64)
+            }
+            TimestampFormat::Iso8601 => {
+                let (seconds, nanos) = parse_iso8601(field).ok_or_else(invalid)?;
+                Ok(seconds * 1_000 + nanos / 1_000_000)
+            }
+        }
+    }
+
+    /// Applies the side of the taker to the unsigned size
+    pub(crate) fn signed_size(&self, side: &str, size: f64) -> Result<f64> {
+        self.side_encoding.signed_size(side, size)
+    }
+}
+
+/// Convert a timestamp of the given resolution to milliseconds, truncating any finer resolution
+///
+/// # Returns:
+/// None if the timestamp does not fit into milliseconds
+fn to_milliseconds(timestamp: i64, resolution: TimestampResolution) -> Option<i64> {
+    match resolution {
+        TimestampResolution::Second => timestamp.checked_mul(1_000),
+        TimestampResolution::Millisecond => Some(timestamp),
+        TimestampResolution::Microsecond => Some(timestamp.div_euclid(1_000)),
+        TimestampResolution::Nanosecond => Some(timestamp.div_euclid(1_000_000)),
+    }
+}
+
+/// Parse an ISO-8601 date and time, such as `2018-01-01T00:00:02.610+01:00`
+///
+/// # Returns:
+/// The seconds since the unix epoch and the nanoseconds within that second,
+/// or None if the timestamp is malformed
+fn parse_iso8601(s: &str) -> Option<(i64, i64)> {
+    let bytes = s.as_bytes();
+    let number = |range: std::ops::Range<usize>| -> Option<i64> {
+        let digits = s.get(range)?;
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok()
+    };
+    let separator = |i: usize, expected: &[u8]| bytes.get(i).is_some_and(|b| expected.contains(b));
+
+    if !(separator(4, b"-") && separator(7, b"-") && separator(10, b"TD ") && separator(13, b":")) {
+        return None;
+    }
+    let year = number(0..4)?;
+    let month = number(5..7)?;
+    let day = number(8..10)?;
+    let hour = number(11..13)?;
+    let minute = number(14..16)?;
+    if !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || hour > 23
+        || minute > 59
+    {
+        return None;
+    }
+
+    // The seconds and their fraction are optional
+    let mut i = 16;
+    let mut second = 0;
+    let mut nanos = 0;
+    if separator(i, b":") {
+        second = number(17..19)?;
+        i = 19;
+        if separator(i, b".,") {
+            let digits = bytes[i + 1..]
+                .iter()
+                .take_while(|b| b.is_ascii_digit())
+                .count();
+            if digits == 0 {
+                return None;
+            }
+            // Digits beyond nanoseconds are truncated
+            let fraction = number(i + 1..i + 1 + digits.min(9))?;
+            nanos = fraction * 10_i64.pow(9 - digits.min(9) as u32);
+            i += 1 + digits;
+        }
+    }
+    if second > 60 {
+        return None;
+    }
+
+    let offset = match &s[i..] {
+        "" | "Z" | "z" => 0,
+        tz => {
+            let sign = match tz.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let (hours, minutes) = match tz.len() {
+                3 => (number(i + 1..i + 3)?, 0),
+                5 => (number(i + 1..i + 3)?, number(i + 3..i + 5)?),
+                6 if separator(i + 3, b":") => (number(i + 1..i + 3)?, number(i + 4..i + 6)?),
+                _ => return None,
+            };
+            sign * (hours * 3600 + minutes * 60)
+        }
+    };
+
+    let seconds = days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second;
+    Some((seconds - offset, nanos))
+}
+
+/// The number of days of a month in the proleptic gregorian calendar
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The number of days since the unix epoch of a date in the proleptic gregorian calendar,
+/// see <http://howardhinnant.github.io/date_algorithms.html#days_from_civil>
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iso8601() {
+        assert_eq!(parse_iso8601("1970-01-01T00:00:00Z"), Some((0, 0)));
+        assert_eq!(
+            parse_iso8601("2018-01-01T00:00:02.610Z"),
+            Some((1_514_764_802, 610_000_000))
+        );
+        // Bitmex dumps separate the date and time with a `D` and use nanoseconds
+        assert_eq!(
+            parse_iso8601("2018-01-01D00:00:02.610123456"),
+            Some((1_514_764_802, 610_123_456))
+        );
+        assert_eq!(
+            parse_iso8601("2018-01-01 01:00:02+01:00"),
+            Some((1_514_764_802, 0))
+        );
+        assert_eq!(
+            parse_iso8601("2017-12-31T23:00-0100"),
+            Some((1_514_764_800, 0))
+        );
+        assert_eq!(
+            parse_iso8601("2020-02-29T12:00:00"),
+            Some((1_582_977_600, 0))
+        );
+        assert_eq!(parse_iso8601("1969-12-31T23:59:59Z"), Some((-1, 0)));
+
+        assert_eq!(parse_iso8601("2018-01-01"), None);
+        assert_eq!(parse_iso8601("2018-13-01T00:00:00Z"), None);
+        assert_eq!(parse_iso8601("2018-04-31T00:00:00Z"), None);
+        assert_eq!(parse_iso8601("2019-02-29T00:00:00Z"), None);
+        assert_eq!(parse_iso8601("1900-02-29T00:00:00Z"), None);
+        assert!(parse_iso8601("2000-02-29T00:00:00Z").is_some());
+        assert_eq!(parse_iso8601("2018-01-01T00:00:00."), None);
+        assert_eq!(parse_iso8601("2018-01-01T00:00:00X"), None);
+        assert_eq!(parse_iso8601("2018-01-01T0a:00:00"), None);
+    }
+
+    #[test]
+    fn csv_schema_parse_timestamp() {
+        let schema = CsvSchema::new().with_timestamp_format(TimestampFormat::FloatSeconds);
+        assert_eq!(
+            schema.parse_timestamp("1514764802.61").unwrap(),
+            1_514_764_802_610
+        );
+        for timestamp in ["NaN", "inf", "-inf", "1e300"] {
+            assert!(schema.parse_timestamp(timestamp).is_err());
+        }
+
+        // All timestamps are converted to the milliseconds of a `Trade`
+        let schema = CsvSchema::new().with_timestamp_format(TimestampFormat::Iso8601);
+        assert_eq!(
+            schema
+                .parse_timestamp("2018-01-01D00:00:02.610123456")
+                .unwrap(),
+            1_514_764_802_610
+        );
+        assert!(schema.parse_timestamp("yesterday").is_err());
+
+        for (resolution, timestamp, expected) in [
+            (TimestampResolution::Second, "1514764802", 1_514_764_802_000),
+            (
+                TimestampResolution::Millisecond,
+                "1514764802610",
+                1_514_764_802_610,
+            ),
+            (
+                TimestampResolution::Microsecond,
+                "1514764802610123",
+                1_514_764_802_610,
+            ),
+            (
+                TimestampResolution::Nanosecond,
+                "1514764802610123456",
+                1_514_764_802_610,
+            ),
+        ] {
+            let schema = CsvSchema::new().with_timestamp_resolution(resolution);
+            assert_eq!(schema.parse_timestamp(timestamp).unwrap(), expected);
+
+            // Regardless of the resolution of the schema
+            let schema = CsvSchema::new().with_timestamp_format(TimestampFormat::InferredInteger);
+            assert_eq!(schema.parse_timestamp(timestamp).unwrap(), expected);
+        }
+
+        // Seconds that overflow the milliseconds of a `Trade`
+        let schema = CsvSchema::new().with_timestamp_resolution(TimestampResolution::Second);
+        assert!(matches!(
+            schema.parse_timestamp("9223372036854775807"),
+            Err(Error::InvalidTimestamp(_))
+        ));
+    }
+
+    #[test]
+    fn csv_schema_signed_size() {
+        let schema = CsvSchema::new();
+        assert_eq!(schema.signed_size("Buy", 2.0).unwrap(), 2.0);
+        assert_eq!(schema.signed_size("sell", 2.0).unwrap(), -2.0);
+        assert!(schema.signed_size("hold", 2.0).is_err());
+
+        let schema = CsvSchema::new().with_side_encoding(SideEncoding::Maker);
+        assert_eq!(schema.signed_size("buy", 2.0).unwrap(), -2.0);
+        assert_eq!(schema.signed_size("sell", -2.0).unwrap(), 2.0);
+
+        // A trade at the bid is a taker sell, a trade at the ask a taker buy
+        for side_encoding in [SideEncoding::Taker, SideEncoding::Maker] {
+            let schema = CsvSchema::new().with_side_encoding(side_encoding);
+            assert_eq!(schema.signed_size("Bid", 2.0).unwrap(), -2.0);
+            assert_eq!(schema.signed_size("ask", -2.0).unwrap(), 2.0);
+        }
+
+        let schema = CsvSchema::new().with_side_encoding(SideEncoding::BuyerIsMaker);
+        assert_eq!(schema.signed_size("True", 2.0).unwrap(), -2.0);
+        assert_eq!(schema.signed_size("false", 2.0).unwrap(), 2.0);
+        assert!(schema.signed_size("buy", 2.0).is_err());
+    }
+}
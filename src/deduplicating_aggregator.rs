@@ -0,0 +1,268 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    marker::PhantomData,
+};
+
+use crate::{Aggregator, Error, ModularCandle, Result, TakerTrade};
+
+/// Determines how a `DeduplicatingAggregator` remembers the trades it has already seen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeduplicationStrategy {
+    /// Remembers the ids of the given number of most recent trades,
+    /// which suits feeds whose trade ids are unique, but not ordered
+    Window(usize),
+
+    /// Remembers the highest id seen so far, ignoring all trades with a lower or equal id,
+    /// which suits feeds whose trade ids increase monotonically
+    Watermark,
+}
+
+/// Ignores trades that have already been seen, as identified by `TakerTrade::trade_id`,
+/// e.g.: trades replayed by a reconnecting websocket feed or an overlapping REST backfill,
+/// which would otherwise be counted twice by components such as `Volume` and `NumTrades`.
+/// Trades without an id are always passed on to the inner aggregator.
+#[derive(Debug, Clone)]
+pub struct DeduplicatingAggregator<A, C, T> {
+    aggregator: A,
+    strategy: DeduplicationStrategy,
+
+    // The ids of the most recent trades, in order of arrival, used by `DeduplicationStrategy::Window`
+    window: VecDeque<u64>,
+    seen: HashSet<u64>,
+
+    // The highest id seen so far, used by `DeduplicationStrategy::Watermark`
+    watermark: Option<u64>,
+
+    num_duplicates: u64,
+
+    candle_type: PhantomData<C>,
+    trade_type: PhantomData<T>,
+}
+
+impl<A, C, T> DeduplicatingAggregator<A, C, T>
+where
+    A: Aggregator<C, T>,
+    C: ModularCandle<T>,
+    T: TakerTrade,
+{
+    /// Create a new instance, passing the trades not seen before to `aggregator`
+    ///
+    /// # Arguments:
+    /// aggregator: The inner aggregator receiving the unique trades
+    /// strategy: How the trades already seen are remembered.
+    /// The size of a `DeduplicationStrategy::Window` must be greater than zero.
+    ///
+    pub fn new(aggregator: A, strategy: DeduplicationStrategy) -> Result<Self> {
+        let capacity = match strategy {
+            DeduplicationStrategy::Window(0) => return Err(Error::InvalidParam),
+            DeduplicationStrategy::Window(size) => size,
+            DeduplicationStrategy::Watermark => 0,
+        };
+        Ok(Self {
+            aggregator,
+            strategy,
+            window: VecDeque::with_capacity(capacity),
+            seen: HashSet::with_capacity(capacity),
+            watermark: None,
+            num_duplicates: 0,
+            candle_type: PhantomData,
+            trade_type: PhantomData,
+        })
+    }
+
+    /// The number of trades which have been ignored, as they have already been seen
+    pub fn num_duplicates(&self) -> u64 {
+        self.num_duplicates
+    }
+
+    /// Remembers the trade
+    ///
+    /// # Returns:
+    /// Whether the trade has already been seen
+    fn is_duplicate(&mut self, trade: &T) -> bool {
+        let Some(id) = trade.trade_id() else {
+            return false;
+        };
+        let is_duplicate = match self.strategy {
+            DeduplicationStrategy::Window(size) => {
+                if self.seen.contains(&id) {
+                    true
+                } else {
+                    if self.window.len() == size {
+                        let oldest = self.window.pop_front().expect("The window is not empty");
+                        self.seen.remove(&oldest);
+                    }
+                    self.window.push_back(id);
+                    self.seen.insert(id);
+                    false
+                }
+            }
+            DeduplicationStrategy::Watermark => {
+                if self.watermark.is_some_and(|watermark| id <= watermark) {
+                    true
+                } else {
+                    self.watermark = Some(id);
+                    false
+                }
+            }
+        };
+        if is_duplicate {
+            self.num_duplicates += 1;
+        }
+        is_duplicate
+    }
+}
+
+impl<A, C, T> Aggregator<C, T> for DeduplicatingAggregator<A, C, T>
+where
+    A: Aggregator<C, T>,
+    C: ModularCandle<T>,
+    T: TakerTrade,
+{
+    fn update(&mut self, trade: &T) -> Option<C> {
+        if self.is_duplicate(trade) {
+            return None;
+        }
+        self.aggregator.update(trade)
+    }
+
+    fn update_into(&mut self, trade: &T, candles: &mut Vec<C>) {
+        if self.is_duplicate(trade) {
+            return;
+        }
+        self.aggregator.update_into(trade, candles);
+    }
+
+    fn advance_time(&mut self, timestamp: i64) -> Option<C> {
+        self.aggregator.advance_time(timestamp)
+    }
+
+    fn advance_time_into(&mut self, timestamp: i64, candles: &mut Vec<C>) {
+        self.aggregator.advance_time_into(timestamp, candles);
+    }
+
+    fn flush_into(&mut self, candles: &mut Vec<C>) {
+        self.aggregator.flush_into(candles);
+    }
+
+    fn finish(&mut self) -> Option<C> {
+        self.aggregator.finish()
+    }
+
+    fn finish_into(&mut self, candles: &mut Vec<C>) {
+        self.aggregator.finish_into(candles);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use trade_aggregation_derive::Candle;
+
+    use super::*;
+    use crate::{
+        aggregate_all_trades,
+        candle_components::{CandleComponent, CandleComponentUpdate, NumTrades, Volume},
+        load_trades_from_csv, GenericAggregator, TickRule, TimeRule, TimestampResolution, Trade,
+        M1,
+    };
+
+    #[derive(Debug, Clone, Copy)]
+    struct IdTrade {
+        id: Option<u64>,
+        trade: Trade,
+    }
+
+    impl TakerTrade for IdTrade {
+        fn timestamp(&self) -> i64 {
+            self.trade.timestamp
+        }
+
+        fn price(&self) -> f64 {
+            self.trade.price
+        }
+
+        fn size(&self) -> f64 {
+            self.trade.size
+        }
+
+        fn trade_id(&self) -> Option<u64> {
+            self.id
+        }
+    }
+
+    #[derive(Debug, Default, Clone, Candle)]
+    struct MyCandle {
+        volume: Volume,
+        num_trades: NumTrades<u32>,
+        input: PhantomData<IdTrade>,
+    }
+
+    #[test]
+    fn deduplicating_aggregator_invalid_param() {
+        let aggregator = GenericAggregator::<MyCandle, _, IdTrade>::new(TickRule::new(10));
+        assert!(
+            DeduplicatingAggregator::new(aggregator, DeduplicationStrategy::Window(0)).is_err()
+        );
+    }
+
+    #[test]
+    fn deduplicating_aggregator() {
+        let trades: Vec<IdTrade> = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv")
+            .unwrap()
+            .into_iter()
+            .enumerate()
+            .map(|(i, trade)| IdTrade {
+                id: Some(i as u64),
+                trade,
+            })
+            .collect();
+        let rule = TimeRule::new(M1, TimestampResolution::Millisecond);
+
+        let mut aggregator = GenericAggregator::<MyCandle, _, IdTrade>::new(rule.clone());
+        let expected = aggregate_all_trades(&trades, &mut aggregator);
+
+        // Replay the previous 500 trades after every 10000 trades, as a reconnecting feed would
+        let mut replayed = vec![];
+        for (i, chunk) in trades.chunks(10_000).enumerate() {
+            if i > 0 {
+                let start = i * 10_000;
+                replayed.extend_from_slice(&trades[start - 500..start]);
+            }
+            replayed.extend_from_slice(chunk);
+        }
+
+        for strategy in [
+            DeduplicationStrategy::Window(1000),
+            DeduplicationStrategy::Watermark,
+        ] {
+            let inner = GenericAggregator::<MyCandle, _, IdTrade>::new(rule.clone());
+            let mut aggregator = DeduplicatingAggregator::new(inner, strategy).unwrap();
+            let candles = aggregate_all_trades(&replayed, &mut aggregator);
+            assert_eq!(
+                aggregator.num_duplicates(),
+                (replayed.len() - trades.len()) as u64
+            );
+
+            assert_eq!(candles.len(), expected.len());
+            for (c, e) in candles.iter().zip(expected.iter()) {
+                assert_eq!(c.volume(), e.volume());
+                assert_eq!(c.num_trades(), e.num_trades());
+            }
+        }
+    }
+
+    #[test]
+    fn deduplicating_aggregator_without_ids() {
+        let trade = IdTrade {
+            id: None,
+            trade: Trade::default(),
+        };
+        let inner = GenericAggregator::<MyCandle, _, IdTrade>::new(TickRule::new(2));
+        let mut aggregator =
+            DeduplicatingAggregator::new(inner, DeduplicationStrategy::Watermark).unwrap();
+        assert!(aggregator.update(&trade).is_none());
+        assert!(aggregator.update(&trade).is_some());
+        assert_eq!(aggregator.num_duplicates(), 0);
+    }
+}
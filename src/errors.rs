@@ -16,6 +16,29 @@ pub enum Error {
 
     #[error("An invalid parameter was provided")]
     InvalidParam,
+
+    #[error("The csv data has no column {0}")]
+    MissingColumn(String),
+
+    #[error("Invalid timestamp: {0}")]
+    InvalidTimestamp(String),
+
+    #[error("Invalid trade side: {0}")]
+    InvalidSide(String),
+
+    #[error("Reading {0} compressed files requires the {0} feature")]
+    UnsupportedCompression(&'static str),
+
+    #[error("The column {0} contains null values")]
+    NullValues(String),
+
+    #[cfg(feature = "arrow")]
+    #[error(transparent)]
+    Arrow(#[from] arrow_schema::ArrowError),
+
+    #[cfg(feature = "parquet")]
+    #[error(transparent)]
+    Parquet(#[from] parquet::errors::ParquetError),
 }
 
 /// Convenient wrapper for this crates custom Error
@@ -0,0 +1,80 @@
+/// Exponentially weighted moving average, parameterized by its span
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ewma {
+    init: bool,
+    alpha: f64,
+    value: f64,
+}
+
+impl Ewma {
+    // new creates an instance where alpha = 2 / (span + 1)
+    pub fn new(span: usize) -> Self {
+        Ewma {
+            init: true,
+            alpha: 2.0 / (span as f64 + 1.0),
+            value: 0.0,
+        }
+    }
+
+    // with_initial_value creates an instance which is already seeded with a value
+    pub fn with_initial_value(span: usize, value: f64) -> Self {
+        let mut ewma = Ewma::new(span);
+        ewma.add(value);
+        ewma
+    }
+
+    // value returns the current average
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    // add updates the average with a new value
+    pub fn add(&mut self, val: f64) {
+        if self.init {
+            self.value = val;
+            self.init = false;
+            return;
+        }
+        self.value += self.alpha * (val - self.value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ewma() {
+        let mut ewma = Ewma::new(3);
+        assert_eq!(ewma.value(), 0.0);
+
+        // The first value seeds the average
+        ewma.add(1.0);
+        assert_eq!(ewma.value(), 1.0);
+
+        // alpha = 0.5
+        ewma.add(2.0);
+        assert_eq!(ewma.value(), 1.5);
+        ewma.add(2.0);
+        assert_eq!(ewma.value(), 1.75);
+
+        let ewma = Ewma::with_initial_value(10, 5.0);
+        assert_eq!(ewma.value(), 5.0);
+    }
+
+    #[test]
+    fn ewma_values() {
+        // alpha = 2 / (7 + 1) = 0.25
+        let mut ewma = Ewma::with_initial_value(7, 4.0);
+        for (val, expected) in [(8.0, 5.0), (0.0, 3.75), (-4.0, 1.8125), (1.8125, 1.8125)] {
+            ewma.add(val);
+            assert_eq!(ewma.value(), expected);
+        }
+
+        // A span of one only keeps the latest value
+        let mut ewma = Ewma::with_initial_value(1, 4.0);
+        ewma.add(-2.0);
+        assert_eq!(ewma.value(), -2.0);
+    }
+}
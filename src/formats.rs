@@ -0,0 +1,229 @@
+//! Parsers for the public trade dumps of various exchanges,
+//! built on top of a `CsvSchema` describing the layout of each of them.
+//! All of them yield `Trade`s with millisecond timestamps, whose size is negative for taker sells.
+//! The sizes are denoted in the units of the exchange,
+//! e.g.: contracts worth 1 USD each for inverse perpetuals on Bybit, Deribit and Bitmex,
+//! so pick `By` and `ContractType` accordingly when aggregating.
+//! The trade ids of the dumps containing integer ids are read by `TradeFileFormat::load_with_trade_ids`.
+
+use std::io::Read;
+
+use crate::{
+    open_trade_file, CsvSchema, IdentifiedTrade, Result, SideEncoding, TimestampFormat, Trade,
+    TradeCsvReader,
+};
+
+/// The layout of the public trade dumps of an exchange
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TradeFileFormat {
+    /// Binance spot `trades` from data.binance.vision, which have no header row:
+    /// id, price, qty, quote_qty, time, is_buyer_maker, is_best_match.
+    /// The USD-M futures dumps have a header row, so use `schema().with_headers(true)` for them.
+    /// Since 2025, the spot dumps use microsecond timestamps instead of milliseconds,
+    /// which is inferred from their magnitude, see `TimestampFormat::InferredInteger`.
+    BinanceTrades,
+
+    /// Binance spot `aggTrades` from data.binance.vision, which have no header row:
+    /// agg_trade_id, price, quantity, first_trade_id, last_trade_id, transact_time, is_buyer_maker, is_best_match.
+    /// The timestamps are handled as for `TradeFileFormat::BinanceTrades`.
+    BinanceAggTrades,
+
+    /// Bybit trading history from public.bybit.com,
+    /// with decimal second timestamps and the side of the taker
+    Bybit,
+
+    /// OKX trade history from the OKX historical data page, with millisecond timestamps
+    Okx,
+
+    /// Coinbase trades as returned by the Exchange API, written to csv with their field names,
+    /// with ISO-8601 timestamps and the side of the maker
+    Coinbase,
+
+    /// Kraken time and sales, which have no header row: time in seconds, price, volume.
+    /// They do not contain the side of the taker, so all sizes are positive.
+    Kraken,
+
+    /// Deribit trades as returned by the history API, written to csv with their field names,
+    /// with millisecond timestamps
+    Deribit,
+
+    /// Bitmex trades from public.bitmex.com,
+    /// with ISO-8601 timestamps whose date and time are separated by a `D`
+    Bitmex,
+}
+
+impl TradeFileFormat {
+    /// The layout of the trade dumps, which can be adjusted further, e.g.: to change the timestamp resolution
+    pub fn schema(&self) -> CsvSchema {
+        match self {
+            TradeFileFormat::BinanceTrades => CsvSchema::new()
+                .with_columns(4, 1, 2)
+                .with_side_column(5)
+                .with_side_encoding(SideEncoding::BuyerIsMaker)
+                .with_trade_id_column(0)
+                .with_headers(false)
+                .with_timestamp_format(TimestampFormat::InferredInteger),
+            TradeFileFormat::BinanceAggTrades => CsvSchema::new()
+                .with_columns(5, 1, 2)
+                .with_side_column(6)
+                .with_side_encoding(SideEncoding::BuyerIsMaker)
+                .with_trade_id_column(0)
+                .with_headers(false)
+                .with_timestamp_format(TimestampFormat::InferredInteger),
+            TradeFileFormat::Bybit => CsvSchema::new()
+                .with_columns("timestamp", "price", "size")
+                .with_side_column("side")
+                .with_timestamp_format(TimestampFormat::FloatSeconds),
+            TradeFileFormat::Okx => CsvSchema::new()
+                .with_columns("created_time", "price", "size")
+                .with_side_column("side")
+                .with_trade_id_column("trade_id"),
+            TradeFileFormat::Coinbase => CsvSchema::new()
+                .with_columns("time", "price", "size")
+                .with_side_column("side")
+                .with_side_encoding(SideEncoding::Maker)
+                .with_trade_id_column("trade_id")
+                .with_timestamp_format(TimestampFormat::Iso8601),
+            TradeFileFormat::Kraken => CsvSchema::new()
+                .with_columns(0, 1, 2)
+                .with_headers(false)
+                .with_timestamp_format(TimestampFormat::FloatSeconds),
+            TradeFileFormat::Deribit => CsvSchema::new()
+                .with_columns("timestamp", "price", "amount")
+                .with_side_column("direction")
+                .with_trade_id_column("trade_id"),
+            TradeFileFormat::Bitmex => CsvSchema::new()
+                .with_columns("timestamp", "price", "size")
+                .with_side_column("side")
+                .with_timestamp_format(TimestampFormat::Iso8601),
+        }
+    }
+
+    /// Create a reader yielding the trades of a dump one at a time, see `TradeCsvReader`
+    pub fn reader<R: Read>(&self, reader: R) -> Result<TradeCsvReader<R>> {
+        TradeCsvReader::with_schema(reader, self.schema())
+    }
+
+    /// Load all trades of a dump at the given path, which may be compressed, see `open_trade_file`
+    ///
+    /// # Arguments:
+    /// filename: The path to the csv file
+    ///
+    /// # Returns
+    /// If Ok, A vector of the trades inside the file
+    pub fn load(&self, filename: &str) -> Result<Vec<Trade>> {
+        self.reader(open_trade_file(filename)?)?.collect()
+    }
+
+    /// Load all trades of a dump at the given path along with their ids, see `TradeCsvReader::with_trade_ids`.
+    /// The ids are None for the dumps without integer trade ids, i.e.: Bybit, Kraken and Bitmex.
+    ///
+    /// # Arguments:
+    /// filename: The path to the csv file
+    ///
+    /// # Returns
+    /// If Ok, A vector of the trades inside the file
+    pub fn load_with_trade_ids(&self, filename: &str) -> Result<Vec<IdentifiedTrade>> {
+        self.reader(open_trade_file(filename)?)?
+            .with_trade_ids()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(timestamp: i64, price: f64, size: f64) -> Trade {
+        Trade {
+            timestamp,
+            price,
+            size,
+        }
+    }
+
+    #[test]
+    fn trade_file_formats() {
+        // Rows written by hand, following the layout of the dumps of each exchange
+        let cases = [
+            (
+                TradeFileFormat::BinanceTrades,
+                "binance_trades",
+                trade(1_704_067_200_035, 42283.58, -0.001),
+                Some(3_366_340_234),
+            ),
+            (
+                TradeFileFormat::BinanceTrades,
+                "binance_trades_2025",
+                trade(1_735_689_600_003, 93576.0, 0.00013),
+                Some(4_402_713_445),
+            ),
+            (
+                TradeFileFormat::BinanceAggTrades,
+                "binance_agg_trades",
+                trade(1_704_067_200_035, 42283.58, -0.001),
+                Some(2_994_396_367),
+            ),
+            (
+                TradeFileFormat::Bybit,
+                "bybit",
+                trade(1_704_067_200_176, 42296.5, -100.0),
+                None,
+            ),
+            (
+                TradeFileFormat::Okx,
+                "okx",
+                trade(1_704_067_200_118, 42286.1, -0.01),
+                Some(469_315_236),
+            ),
+            (
+                TradeFileFormat::Coinbase,
+                "coinbase",
+                trade(1_704_067_200_151, 42288.58, 0.0105),
+                Some(588_913_745),
+            ),
+            (
+                TradeFileFormat::Kraken,
+                "kraken",
+                trade(1_704_067_200_000, 42282.1, 0.001183),
+                None,
+            ),
+            (
+                TradeFileFormat::Deribit,
+                "deribit",
+                trade(1_704_067_200_371, 42290.5, 1000.0),
+                Some(286_871_374),
+            ),
+            (
+                TradeFileFormat::Bitmex,
+                "bitmex",
+                trade(1_704_067_200_418, 42295.5, 100.0),
+                None,
+            ),
+        ];
+        for (format, name, first, first_id) in cases {
+            let filename = format!("data/formats/{name}.csv");
+            let trades = format
+                .load_with_trade_ids(&filename)
+                .unwrap_or_else(|e| panic!("{name}: {e}"));
+            assert_eq!(trades.len(), 3, "{name}");
+            assert_eq!(trades[0].trade, first, "{name}");
+            for (i, t) in trades.iter().enumerate() {
+                assert_eq!(t.trade_id, first_id.map(|id| id + i as u64), "{name}");
+            }
+            assert!(
+                trades
+                    .windows(2)
+                    .all(|w| w[0].trade.timestamp <= w[1].trade.timestamp),
+                "{name}"
+            );
+
+            let plain = format.load(&filename).unwrap();
+            assert_eq!(plain.len(), trades.len(), "{name}");
+            for (p, t) in plain.iter().zip(trades.iter()) {
+                assert_eq!(*p, t.trade, "{name}");
+            }
+        }
+    }
+}
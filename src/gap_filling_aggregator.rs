@@ -0,0 +1,374 @@
+use std::collections::VecDeque;
+
+use crate::{
+    AggregationRule, Aggregator, GenericAggregator, ModularCandle, PeriodicRule, TakerTrade,
+};
+
+/// An `Aggregator` that emits synthetic candles for every period without any trades,
+/// such that the candles form a regular time grid.
+/// It wraps a `GenericAggregator` with a time based rule such as `TimeRule` or `AlignedTimeRule`.
+/// Before every trade, time is advanced to its timestamp, see `Aggregator::advance_time`,
+/// so the candle of the previous period is closed, followed by a synthetic candle for every period in between.
+/// Calling `advance_time` without a trade emits the synthetic candles up to the given time.
+///
+/// A synthetic candle does not observe any trade, see `ModularCandle::fill_gap`,
+/// so its open, high, low and close prices equal the previous close,
+/// while components describing the trades, such as `Volume`, `NumTrades` and `Trades`, are empty.
+///
+/// As a single trade can create multiple candles, use `update_into` to receive all of them at once.
+/// `update`, `advance_time` and `finish` return one candle per call,
+/// keeping the others for the following calls.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GapFillingAggregator<C, R, T> {
+    aggregator: GenericAggregator<C, R, T>,
+
+    // The price of the most recent trade
+    last_price: f64,
+
+    // The start of the first period not covered by a candle yet,
+    // if the candle of the previous period has been closed without a trade opening the next one
+    next_period: Option<i64>,
+
+    // The candles created, but not yet returned by `update`, `advance_time` or `finish`
+    pending: VecDeque<C>,
+}
+
+impl<C, R, T> GapFillingAggregator<C, R, T>
+where
+    C: ModularCandle<T>,
+    R: AggregationRule<C, T> + PeriodicRule,
+    T: TakerTrade,
+{
+    /// Create a new instance, filling the gaps of the candles created by `aggregator`
+    pub fn new(aggregator: GenericAggregator<C, R, T>) -> Self {
+        Self {
+            aggregator,
+            last_price: 0.0,
+            next_period: None,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Create a synthetic candle for the period starting at `timestamp`
+    fn gap_candle(&self, timestamp: i64) -> C {
+        let mut candle = C::default();
+        candle.fill_gap(timestamp, self.last_price);
+        candle
+    }
+
+    /// Advance the time to `timestamp`, pushing the candle closed by it
+    /// and the synthetic candles of all periods that ended since into `candles`
+    fn process_time(&mut self, timestamp: i64, candles: &mut Vec<C>) {
+        let period = self.aggregator.aggregation_rule().period();
+        let reference = self.aggregator.aggregation_rule().reference_timestamp();
+
+        let num_candles = candles.len();
+        self.aggregator.advance_time_into(timestamp, candles);
+        if candles.len() > num_candles {
+            self.next_period = Some(reference + period);
+        }
+
+        if let Some(mut start) = self.next_period {
+            while start + period <= timestamp {
+                candles.push(self.gap_candle(start));
+                start += period;
+            }
+            self.next_period = Some(start);
+        }
+    }
+
+    /// Process a trade, pushing all candles created by it into `candles`
+    fn process(&mut self, trade: &T, candles: &mut Vec<C>) {
+        // The candle of the previous period is closed before the trade opens the next one
+        self.process_time(trade.timestamp(), candles);
+        self.aggregator.update_into(trade, candles);
+        self.next_period = None;
+
+        self.last_price = trade.price();
+    }
+
+    /// The oldest pending candle, after adding the given candles to the pending ones
+    fn next_pending(&mut self, candles: Vec<C>) -> Option<C> {
+        self.pending.extend(candles);
+        self.pending.pop_front()
+    }
+}
+
+impl<C, R, T> Aggregator<C, T> for GapFillingAggregator<C, R, T>
+where
+    C: ModularCandle<T>,
+    R: AggregationRule<C, T> + PeriodicRule,
+    T: TakerTrade,
+{
+    fn update(&mut self, trade: &T) -> Option<C> {
+        let mut candles = vec![];
+        self.process(trade, &mut candles);
+        self.next_pending(candles)
+    }
+
+    fn update_into(&mut self, trade: &T, candles: &mut Vec<C>) {
+        candles.extend(self.pending.drain(..));
+        self.process(trade, candles);
+    }
+
+    fn advance_time(&mut self, timestamp: i64) -> Option<C> {
+        let mut candles = vec![];
+        self.process_time(timestamp, &mut candles);
+        self.next_pending(candles)
+    }
+
+    fn advance_time_into(&mut self, timestamp: i64, candles: &mut Vec<C>) {
+        candles.extend(self.pending.drain(..));
+        self.process_time(timestamp, candles);
+    }
+
+    fn flush_into(&mut self, candles: &mut Vec<C>) {
+        candles.extend(self.pending.drain(..));
+        self.aggregator.flush_into(candles);
+    }
+
+    fn finish(&mut self) -> Option<C> {
+        self.next_period = None;
+        let candle = self.aggregator.finish();
+        self.next_pending(candle.into_iter().collect())
+    }
+
+    fn finish_into(&mut self, candles: &mut Vec<C>) {
+        candles.extend(self.pending.drain(..));
+        self.next_period = None;
+        self.aggregator.finish_into(candles);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use trade_aggregation_derive::Candle;
+
+    use super::*;
+    use crate::{
+        candle_components::{
+            CandleComponent, CandleComponentUpdate, Close, High, Low, NumTrades, Open,
+            OpenTimeStamp, Volume,
+        },
+        load_trades_from_csv, AlignedTimeRule, TimeRule, TimestampResolution, Trade, M1,
+    };
+
+    #[derive(Debug, Default, Clone, Candle)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    struct MyCandle {
+        open: Open,
+        high: High,
+        low: Low,
+        close: Close,
+        volume: Volume,
+        num_trades: NumTrades<u32>,
+        open_timestamp: OpenTimeStamp<i64>,
+    }
+
+    fn trade(timestamp: i64, price: f64) -> Trade {
+        Trade {
+            timestamp,
+            price,
+            size: 1.0,
+        }
+    }
+
+    fn trades() -> Vec<Trade> {
+        vec![
+            trade(0, 100.0),
+            trade(30, 101.0),
+            trade(61, 102.0),
+            trade(400, 110.0),
+            trade(500, 111.0),
+        ]
+    }
+
+    #[test]
+    fn gap_filling_aggregator() {
+        let rule = AlignedTimeRule::new(M1, TimestampResolution::Second);
+        let mut aggregator =
+            GapFillingAggregator::new(GenericAggregator::<MyCandle, _, Trade>::new(rule));
+
+        let mut candles = vec![];
+        for t in &trades() {
+            aggregator.update_into(t, &mut candles);
+        }
+
+        // The trade at 61 closes the first candle and opens the period at 60.
+        // The trade at 400 closes it and opens the period at 360, so the periods from 120 to 300 are gaps.
+        // The trade at 500 closes it and opens the period at 480, so the period at 420 is a gap
+        let open_timestamps: Vec<i64> = candles.iter().map(|c| c.open_timestamp()).collect();
+        assert_eq!(open_timestamps, vec![0, 61, 120, 180, 240, 300, 400, 420]);
+        assert_eq!(candles[0].open(), 100.0);
+        for c in &candles[2..6] {
+            assert_eq!(c.open(), 102.0);
+            assert_eq!(c.high(), 102.0);
+            assert_eq!(c.low(), 102.0);
+            assert_eq!(c.close(), 102.0);
+            assert_eq!(c.volume(), 0.0);
+            assert_eq!(c.num_trades(), 0);
+        }
+        assert_eq!(candles[7].close(), 110.0);
+        assert_eq!(candles[7].volume(), 0.0);
+        assert_eq!(candles[7].num_trades(), 0);
+    }
+
+    #[test]
+    fn gap_filling_aggregator_update() {
+        let rule = AlignedTimeRule::new(M1, TimestampResolution::Second);
+        let mut aggregator =
+            GapFillingAggregator::new(GenericAggregator::<MyCandle, _, Trade>::new(rule));
+
+        // `update` returns one candle per call, the others follow in order with the next calls
+        let mut candles: Vec<MyCandle> = trades()
+            .iter()
+            .filter_map(|t| aggregator.update(t))
+            .collect();
+        assert_eq!(candles.len(), 3);
+        while let Some(candle) = aggregator.finish() {
+            candles.push(candle);
+        }
+
+        let open_timestamps: Vec<i64> = candles.iter().map(|c| c.open_timestamp()).collect();
+        assert_eq!(
+            open_timestamps,
+            vec![0, 61, 120, 180, 240, 300, 400, 420, 500]
+        );
+    }
+
+    #[test]
+    fn gap_filling_aggregator_advance_time() {
+        let rule = AlignedTimeRule::new(M1, TimestampResolution::Second);
+        let mut aggregator =
+            GapFillingAggregator::new(GenericAggregator::<MyCandle, _, Trade>::new(rule));
+
+        let mut candles = vec![];
+        aggregator.update_into(&trade(10, 100.0), &mut candles);
+        aggregator.advance_time_into(59, &mut candles);
+        assert!(candles.is_empty());
+
+        // The candle is closed on schedule, followed by the gaps up to the current time
+        aggregator.advance_time_into(150, &mut candles);
+        assert_eq!(candles.len(), 2);
+        aggregator.advance_time_into(179, &mut candles);
+        assert_eq!(candles.len(), 2);
+        aggregator.advance_time_into(180, &mut candles);
+        assert_eq!(candles.len(), 3);
+
+        // The next trade only fills the gaps which have not been emitted yet
+        aggregator.update_into(&trade(250, 101.0), &mut candles);
+        aggregator.update_into(&trade(300, 102.0), &mut candles);
+        let open_timestamps: Vec<i64> = candles.iter().map(|c| c.open_timestamp()).collect();
+        assert_eq!(open_timestamps, vec![10, 60, 120, 180, 250]);
+        for c in &candles[1..4] {
+            assert_eq!(c.close(), 100.0);
+            assert_eq!(c.volume(), 0.0);
+        }
+    }
+
+    #[test]
+    fn gap_filling_aggregator_regular_grid() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+
+        let rule = AlignedTimeRule::new(M1, TimestampResolution::Millisecond);
+        let mut aggregator =
+            GapFillingAggregator::new(GenericAggregator::<MyCandle, _, Trade>::new(rule));
+        let mut candles = vec![];
+        for t in &trades {
+            aggregator.update_into(t, &mut candles);
+        }
+
+        // Every period from the first one up to the incomplete last one has exactly one candle
+        let period = M1 * 1000;
+        let first_period = trades[0].timestamp - trades[0].timestamp % period;
+        let last_period =
+            trades.last().unwrap().timestamp - trades.last().unwrap().timestamp % period;
+        assert_eq!(candles.len() as i64, (last_period - first_period) / period);
+        for (i, c) in candles.iter().enumerate() {
+            let start = c.open_timestamp() - c.open_timestamp() % period;
+            assert_eq!(start, first_period + i as i64 * period);
+        }
+    }
+
+    #[test]
+    fn gap_filling_aggregator_time_rule() {
+        let rule = TimeRule::new(M1, TimestampResolution::Second);
+        let mut aggregator =
+            GapFillingAggregator::new(GenericAggregator::<MyCandle, _, Trade>::new(rule));
+
+        let mut candles = vec![];
+        for t in [trade(0, 100.0), trade(61, 102.0), trade(400, 110.0)] {
+            aggregator.update_into(&t, &mut candles);
+        }
+
+        // The trade at 61 opens the second period, which ends at 121.
+        // The periods of 60 seconds from 121 to 301 are gaps, as the next candle starts at 400
+        let open_timestamps: Vec<i64> = candles.iter().map(|c| c.open_timestamp()).collect();
+        assert_eq!(open_timestamps, vec![0, 61, 121, 181, 241, 301]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn gap_filling_aggregator_serde() {
+        type MyAggregator = GapFillingAggregator<MyCandle, AlignedTimeRule, Trade>;
+
+        // A trade for a price, or an advance of time without a trade otherwise
+        let steps = [
+            (0, Some(100.0)),
+            (30, Some(101.0)),
+            (150, None),
+            (200, Some(102.0)),
+            (230, Some(103.0)),
+            (500, None),
+            (520, Some(104.0)),
+            (700, Some(105.0)),
+        ];
+        let run = |aggregator: &mut MyAggregator, steps: &[(i64, Option<f64>)]| {
+            // `update` and `advance_time` keep the candles beyond the first one for the following calls,
+            // so the snapshots include them
+            let mut candles = vec![];
+            for (timestamp, price) in steps {
+                let candle = match price {
+                    Some(price) => aggregator.update(&trade(*timestamp, *price)),
+                    None => aggregator.advance_time(*timestamp),
+                };
+                candles.extend(candle);
+            }
+            candles
+        };
+        let finish = |aggregator: &mut MyAggregator, candles: &mut Vec<MyCandle>| {
+            while let Some(candle) = aggregator.finish() {
+                candles.push(candle);
+            }
+        };
+        let new_aggregator = || {
+            let rule = AlignedTimeRule::new(M1, TimestampResolution::Second);
+            GapFillingAggregator::new(GenericAggregator::<MyCandle, _, Trade>::new(rule))
+        };
+
+        let mut aggregator = new_aggregator();
+        let mut expected = run(&mut aggregator, &steps);
+        finish(&mut aggregator, &mut expected);
+        assert_eq!(expected.len(), 12);
+
+        // Snapshot the aggregator after every step and resume from the snapshot
+        for i in 0..=steps.len() {
+            let mut aggregator = new_aggregator();
+            let mut candles = run(&mut aggregator, &steps[..i]);
+            let snapshot = serde_json::to_string(&aggregator).unwrap();
+
+            let mut aggregator: MyAggregator = serde_json::from_str(&snapshot).unwrap();
+            candles.append(&mut run(&mut aggregator, &steps[i..]));
+            finish(&mut aggregator, &mut candles);
+
+            assert_eq!(candles.len(), expected.len(), "{i}");
+            for (c, e) in candles.iter().zip(expected.iter()) {
+                assert_eq!(c.open_timestamp(), e.open_timestamp(), "{i}");
+                assert_eq!(c.open(), e.open(), "{i}");
+                assert_eq!(c.close(), e.close(), "{i}");
+                assert_eq!(c.num_trades(), e.num_trades(), "{i}");
+            }
+        }
+    }
+}
@@ -11,22 +11,55 @@
 #[cfg(test)]
 mod plot;
 
+mod aggregate_ext;
+#[cfg(feature = "futures")]
+mod aggregate_stream;
 mod aggregation_rules;
 mod aggregator;
 pub mod candle_components;
+#[cfg(feature = "arrow")]
+mod columnar;
 mod constants;
+mod csv_schema;
+mod deduplicating_aggregator;
 mod errors;
+mod ewma;
+pub mod formats;
+mod gap_filling_aggregator;
 mod modular_candle_trait;
+mod multi_aggregator;
+mod multi_timeframe_aggregator;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod reordering_aggregator;
 mod types;
 mod utils;
 mod welford_online;
 
+pub use aggregate_ext::{Aggregate, AggregateExt};
+#[cfg(feature = "futures")]
+pub use aggregate_stream::{AggregateStream, AggregateStreamExt, MarketEvent};
 pub use aggregation_rules::*;
 pub use aggregator::*;
-pub use candle_components::{CandleComponent, CandleComponentUpdate};
+pub use candle_components::{CandleComponent, CandleComponentMerge, CandleComponentUpdate};
+#[cfg(feature = "arrow")]
+pub use columnar::{
+    candles_to_record_batch, load_trades_from_arrow_ipc, trades_from_record_batch,
+    ColumnarTradeReader, TradeColumns,
+};
+#[cfg(feature = "parquet")]
+pub use columnar::{load_trades_from_parquet, write_candles_to_parquet};
 pub use constants::*;
+pub use csv_schema::{CsvColumn, CsvSchema, SideEncoding, TimestampFormat};
+pub use deduplicating_aggregator::{DeduplicatingAggregator, DeduplicationStrategy};
 pub use errors::*;
-pub use modular_candle_trait::ModularCandle;
+pub use gap_filling_aggregator::GapFillingAggregator;
+pub use modular_candle_trait::{CandleColumns, ColumnValue, ModularCandle, ModularCandleMerge};
+pub use multi_aggregator::MultiAggregator;
+pub use multi_timeframe_aggregator::{MultiTimeframeAggregator, TimeframeCandle};
+#[cfg(feature = "rayon")]
+pub use parallel::aggregate_all_trades_parallel;
+pub use reordering_aggregator::ReorderingAggregator;
 pub use trade_aggregation_derive::Candle;
 pub use types::*;
 pub use utils::*;
@@ -9,4 +9,148 @@ pub trait ModularCandle<T: TakerTrade>: Clone + Default {
 
     /// Resets the state of the candle
     fn reset(&mut self);
+
+    /// Updates the candle for a period without any trades, starting at `timestamp`,
+    /// given the price of the last trade, see `CandleComponentUpdate::fill_gap`.
+    /// The default leaves the candle unchanged.
+    fn fill_gap(&mut self, _timestamp: i64, _price: f64) {}
+}
+
+/// A modular candle which can be merged with a later candle,
+/// e.g.: to build hourly candles from minute candles without the original trades.
+/// Derive it using `#[derive(Candle)]` along with the `#[candle(merge)]` attribute.
+/// Note that the trade at the boundary is counted twice when merging candles
+/// created with `BoundaryPolicy::Both`
+pub trait ModularCandleMerge<T: TakerTrade>: ModularCandle<T> {
+    /// Merges `other`, which covers the trades following the ones of `self`,
+    /// such that `self` covers the trades of both candles
+    fn merge(&mut self, other: &Self);
+}
+
+/// The value of a candle component, as stored in a column, see `CandleColumns`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnValue {
+    /// A floating point value, e.g.: a price
+    Float(f64),
+
+    /// A signed integer value, e.g.: a timestamp
+    Int(i64),
+
+    /// An unsigned integer value, e.g.: a number of trades
+    UInt(u64),
+}
+
+macro_rules! impl_from_for_column_value {
+    ($variant:ident, $target:ty, $($t:ty),*) => {
+        $(
+            impl From<$t> for ColumnValue {
+                fn from(value: $t) -> Self {
+                    ColumnValue::$variant(value as $target)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_for_column_value!(Float, f64, f64, f32);
+impl_from_for_column_value!(Int, i64, i64, i32, i16, i8, isize);
+impl_from_for_column_value!(UInt, u64, u64, u32, u16, u8, usize);
+
+/// A candle whose components can be stored as columns, one per component,
+/// e.g.: to write candles to Parquet files.
+/// Derive it using `#[derive(Candle)]` along with the `#[candle(columns)]` attribute,
+/// which requires the value of every component to convert into a `ColumnValue`.
+pub trait CandleColumns {
+    /// The name of every column, in the order of the fields of the candle
+    fn column_names() -> &'static [&'static str];
+
+    /// Passes the value of every component to `push`, along with the index of its column in `column_names`,
+    /// e.g.: to append them to a builder per column without allocating per candle
+    fn write_columns(&self, push: &mut dyn FnMut(usize, ColumnValue));
+}
+
+#[cfg(test)]
+mod tests {
+    use round::round;
+    use trade_aggregation_derive::Candle;
+
+    use super::*;
+    use crate::{
+        aggregate_all_trades,
+        candle_components::{
+            AveragePrice, CandleComponent, CandleComponentMerge, CandleComponentUpdate, Close,
+            CloseTimeStamp, DirectionalTradeRatio, DirectionalVolumeRatio, High, Low, NumTrades,
+            Open, OpenTimeStamp, StdDevPrices, StdDevSizes, Volume, WeightedPrice,
+        },
+        load_trades_from_csv, AlignedTimeRule, GenericAggregator, TimestampResolution, Trade, H1,
+        M1,
+    };
+
+    #[derive(Debug, Default, Clone, Candle)]
+    #[candle(merge)]
+    struct MyCandle {
+        open: Open,
+        high: High,
+        low: Low,
+        close: Close,
+        volume: Volume,
+        num_trades: NumTrades<u32>,
+        weighted_price: WeightedPrice,
+        average_price: AveragePrice,
+        std_dev_prices: StdDevPrices,
+        std_dev_sizes: StdDevSizes,
+        directional_trade_ratio: DirectionalTradeRatio,
+        directional_volume_ratio: DirectionalVolumeRatio,
+        open_timestamp: OpenTimeStamp<i64>,
+        close_timestamp: CloseTimeStamp<i64>,
+    }
+
+    #[test]
+    fn merge_candles() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+
+        let rule = AlignedTimeRule::new(H1, TimestampResolution::Millisecond);
+        let mut aggregator = GenericAggregator::<MyCandle, _, Trade>::new(rule);
+        let hourly = aggregate_all_trades(&trades, &mut aggregator);
+
+        let rule = AlignedTimeRule::new(M1, TimestampResolution::Millisecond);
+        let mut aggregator = GenericAggregator::<MyCandle, _, Trade>::new(rule);
+        let minutes = aggregate_all_trades(&trades, &mut aggregator);
+
+        // Merge the minute candles of every complete hour
+        let hour = H1 * 1000;
+        let mut merged: Vec<MyCandle> = vec![];
+        for m in &minutes {
+            match merged.last_mut() {
+                Some(c) if c.open_timestamp() / hour == m.open_timestamp() / hour => c.merge(m),
+                _ => merged.push(m.clone()),
+            }
+        }
+        merged.truncate(hourly.len());
+        assert_eq!(merged.len(), hourly.len());
+
+        let r = |v: f64| round(v, 6);
+        for (m, h) in merged.iter().zip(hourly.iter()) {
+            assert_eq!(m.open(), h.open());
+            assert_eq!(m.high(), h.high());
+            assert_eq!(m.low(), h.low());
+            assert_eq!(m.close(), h.close());
+            assert_eq!(r(m.volume()), r(h.volume()));
+            assert_eq!(m.num_trades(), h.num_trades());
+            assert_eq!(r(m.weighted_price()), r(h.weighted_price()));
+            assert_eq!(r(m.average_price()), r(h.average_price()));
+            assert_eq!(r(m.std_dev_prices()), r(h.std_dev_prices()));
+            assert_eq!(r(m.std_dev_sizes()), r(h.std_dev_sizes()));
+            assert_eq!(
+                r(m.directional_trade_ratio()),
+                r(h.directional_trade_ratio())
+            );
+            assert_eq!(
+                r(m.directional_volume_ratio()),
+                r(h.directional_volume_ratio())
+            );
+            assert_eq!(m.open_timestamp(), h.open_timestamp());
+            assert_eq!(m.close_timestamp(), h.close_timestamp());
+        }
+    }
 }
@@ -0,0 +1,249 @@
+use std::{collections::HashMap, fmt, hash::Hash};
+
+use crate::{
+    AggregationRule, Aggregator, BoundaryPolicy, GenericAggregator, ModularCandle, TakerTrade,
+};
+
+/// Aggregates the trades of many instruments, e.g.: from a single merged trade stream,
+/// keeping one `GenericAggregator` per key, such as a symbol or a numeric instrument id.
+/// The aggregators are created lazily, once the first trade of an instrument arrives,
+/// using the aggregation rule created by the rule factory for that key.
+/// The rule factory is required to be `Send`, such that the aggregator can be moved to another thread,
+/// e.g.: into the task consuming the trade stream.
+pub struct MultiAggregator<K, C, R, T> {
+    aggregators: HashMap<K, GenericAggregator<C, R, T>>,
+    rule_factory: Box<dyn FnMut(&K) -> R + Send>,
+    boundary_policy: BoundaryPolicy,
+
+    // Reused to receive the candles of a single aggregator, avoiding an allocation per trade
+    buffer: Vec<C>,
+}
+
+impl<K, C, R, T> fmt::Debug for MultiAggregator<K, C, R, T>
+where
+    K: fmt::Debug,
+    C: fmt::Debug,
+    R: fmt::Debug,
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MultiAggregator")
+            .field("aggregators", &self.aggregators)
+            .field("boundary_policy", &self.boundary_policy)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K, C, R, T> MultiAggregator<K, C, R, T>
+where
+    K: Clone + Eq + Hash,
+    C: ModularCandle<T>,
+    R: AggregationRule<C, T>,
+    T: TakerTrade,
+{
+    /// Create a new instance without any instruments
+    ///
+    /// # Arguments:
+    /// rule_factory: Creates the aggregation rule for the instrument with the given key
+    ///
+    pub fn new<F>(rule_factory: F) -> Self
+    where
+        F: FnMut(&K) -> R + Send + 'static,
+    {
+        Self::with_boundary_policy(rule_factory, BoundaryPolicy::default())
+    }
+
+    /// Create a new instance without any instruments
+    ///
+    /// # Arguments:
+    /// rule_factory: Creates the aggregation rule for the instrument with the given key
+    /// boundary_policy: Which candle includes the trade at the boundary, for all instruments
+    ///
+    pub fn with_boundary_policy<F>(rule_factory: F, boundary_policy: BoundaryPolicy) -> Self
+    where
+        F: FnMut(&K) -> R + Send + 'static,
+    {
+        Self {
+            aggregators: HashMap::new(),
+            rule_factory: Box::new(rule_factory),
+            boundary_policy,
+            buffer: vec![],
+        }
+    }
+
+    /// The aggregator of the instrument with the given key, creating it if it does not exist yet
+    fn aggregator(&mut self, key: &K) -> &mut GenericAggregator<C, R, T> {
+        if !self.aggregators.contains_key(key) {
+            let rule = (self.rule_factory)(key);
+            self.aggregators.insert(
+                key.clone(),
+                GenericAggregator::with_boundary_policy(rule, self.boundary_policy),
+            );
+        }
+        self.aggregators.get_mut(key).unwrap()
+    }
+
+    /// Updates the aggregation state of an instrument with a new trade
+    ///
+    /// # Arguments:
+    /// key: the instrument the trade belongs to
+    /// trade: the trade information to add to the aggregation process
+    ///
+    /// # Returns:
+    /// Some output with the key of the instrument only when a new candle has been created,
+    /// otherwise it returns None
+    pub fn update(&mut self, key: &K, trade: &T) -> Option<(K, C)> {
+        self.aggregator(key)
+            .update(trade)
+            .map(|candle| (key.clone(), candle))
+    }
+
+    /// Updates the aggregation state of an instrument with a new trade,
+    /// pushing all candles that have been created by it into `candles`
+    ///
+    /// # Arguments:
+    /// key: the instrument the trade belongs to
+    /// trade: the trade information to add to the aggregation process
+    /// candles: the created candles are appended to it, along with the key of the instrument
+    pub fn update_into(&mut self, key: &K, trade: &T, candles: &mut Vec<(K, C)>) {
+        let mut created = std::mem::take(&mut self.buffer);
+        self.aggregator(key).update_into(trade, &mut created);
+        candles.extend(created.drain(..).map(|candle| (key.clone(), candle)));
+        self.buffer = created;
+    }
+
+    /// Informs the aggregation state of all instruments that time has advanced without a new trade,
+    /// see `Aggregator::advance_time`
+    ///
+    /// # Returns:
+    /// The candles that have been closed, along with the key of their instrument
+    pub fn advance_time(&mut self, timestamp: i64) -> Vec<(K, C)> {
+        let mut candles = vec![];
+        for (key, aggregator) in self.aggregators.iter_mut() {
+            aggregator.advance_time_into(timestamp, &mut self.buffer);
+            candles.extend(self.buffer.drain(..).map(|candle| (key.clone(), candle)));
+        }
+        candles
+    }
+
+    /// Finishes the current aggregation period of all instruments, see `Aggregator::finish`
+    ///
+    /// # Returns:
+    /// The incomplete candles, along with the key of their instrument,
+    /// preceded by the candles of an instrument not returned by `update` yet
+    pub fn finish(&mut self) -> Vec<(K, C)> {
+        let mut candles = vec![];
+        for (key, aggregator) in self.aggregators.iter_mut() {
+            aggregator.finish_into(&mut self.buffer);
+            candles.extend(self.buffer.drain(..).map(|candle| (key.clone(), candle)));
+        }
+        candles
+    }
+
+    /// The keys of all instruments that have received trades
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.aggregators.keys()
+    }
+
+    /// The number of instruments that have received trades
+    pub fn len(&self) -> usize {
+        self.aggregators.len()
+    }
+
+    /// Whether no instrument has received any trades
+    pub fn is_empty(&self) -> bool {
+        self.aggregators.is_empty()
+    }
+
+    /// Removes the aggregator of an instrument, e.g.: once it has been delisted
+    ///
+    /// # Returns:
+    /// The candles of the instrument not returned by `update` yet, followed by its incomplete candle, if any
+    pub fn remove(&mut self, key: &K) -> Vec<C> {
+        let mut candles = vec![];
+        if let Some(mut aggregator) = self.aggregators.remove(key) {
+            aggregator.finish_into(&mut candles);
+        }
+        candles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{aggregate_all_trades, load_trades_from_csv, plot::OhlcCandle, TickRule, Trade};
+
+    #[test]
+    fn multi_aggregator() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+
+        // Pretend every other trade belongs to a different instrument
+        let mut aggregator = MultiAggregator::<&str, OhlcCandle, TickRule, Trade>::new(|key| {
+            TickRule::new(if *key == "XBTUSD" { 1000 } else { 500 })
+        });
+        let mut candles = vec![];
+        for (i, t) in trades.iter().enumerate() {
+            let key = if i % 2 == 0 { "XBTUSD" } else { "ETHUSD" };
+            aggregator.update_into(&key, t, &mut candles);
+        }
+        assert_eq!(aggregator.len(), 2);
+
+        let num_xbt = candles.iter().filter(|(key, _)| *key == "XBTUSD").count();
+        let num_eth = candles.iter().filter(|(key, _)| *key == "ETHUSD").count();
+        assert_eq!(num_xbt, trades.len().div_ceil(2) / 1000);
+        assert_eq!(num_eth, trades.len() / 2 / 500);
+
+        // Each instrument produces the same candles as its own aggregator
+        let xbt_trades: Vec<Trade> = trades.iter().step_by(2).copied().collect();
+        let mut single = GenericAggregator::<OhlcCandle, _, Trade>::new(TickRule::new(1000));
+        let expected = aggregate_all_trades(&xbt_trades, &mut single);
+        for (c, e) in candles
+            .iter()
+            .filter(|(key, _)| *key == "XBTUSD")
+            .map(|(_, c)| c)
+            .zip(expected.iter())
+        {
+            assert_eq!(c.open(), e.open());
+            assert_eq!(c.close(), e.close());
+        }
+
+        assert_eq!(aggregator.finish().len(), 2);
+        assert!(aggregator.finish().is_empty());
+    }
+
+    #[test]
+    fn multi_aggregator_update() {
+        let mut aggregator =
+            MultiAggregator::<u32, OhlcCandle, TickRule, Trade>::new(|_| TickRule::new(2));
+        assert!(aggregator.is_empty());
+
+        let trade = Trade::default();
+        assert!(aggregator.update(&1, &trade).is_none());
+        assert!(aggregator.update(&2, &trade).is_none());
+        assert_eq!(aggregator.update(&1, &trade).map(|(key, _)| key), Some(1));
+        assert_eq!(aggregator.update(&2, &trade).map(|(key, _)| key), Some(2));
+
+        assert_eq!(aggregator.remove(&1).len(), 1);
+        assert_eq!(aggregator.keys().collect::<Vec<_>>(), vec![&2]);
+        assert!(aggregator.remove(&1).is_empty());
+    }
+
+    #[test]
+    fn multi_aggregator_send() {
+        fn assert_send<S: Send>(_: &S) {}
+
+        let mut counter = 0;
+        let aggregator = MultiAggregator::<String, OhlcCandle, TickRule, Trade>::new(move |_| {
+            counter += 1;
+            TickRule::new(counter)
+        });
+        assert_send(&aggregator);
+
+        let handle = std::thread::spawn(move || {
+            let mut aggregator = aggregator;
+            aggregator.update(&"XBTUSD".to_string(), &Trade::default());
+            aggregator.len()
+        });
+        assert_eq!(handle.join().unwrap(), 1);
+    }
+}
@@ -0,0 +1,436 @@
+use crate::{
+    AggregationRule, Aggregator, BoundaryPolicy, GenericAggregator, ModularCandleMerge, TakerTrade,
+};
+
+/// A candle created by a `MultiTimeframeAggregator`, along with the information tying it
+/// to the candles of the other timeframes
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimeframeCandle<C> {
+    /// The index of the timeframe, as returned by `MultiTimeframeAggregator::add_rule`,
+    /// where 0 is the base timeframe built from the trades
+    pub timeframe: usize,
+
+    /// The number of candles of this timeframe created before this one
+    pub sequence: u64,
+
+    /// The index of the trade that closed this candle, counting all trades passed to the aggregator.
+    /// Candles of different timeframes closed by the same trade share it.
+    pub trade_index: u64,
+
+    /// The candle itself
+    pub candle: C,
+}
+
+/// A timeframe built by merging the completed candles of the timeframe below it
+#[derive(Debug, Clone)]
+struct MergedTimeframe<C, R> {
+    rule: R,
+    candle: C,
+
+    // Whether any candle of the timeframe below has been merged into `candle`
+    has_candles: bool,
+}
+
+impl<C, R> MergedTimeframe<C, R> {
+    fn new(rule: R) -> Self
+    where
+        C: Default,
+    {
+        Self {
+            rule,
+            candle: C::default(),
+            has_candles: false,
+        }
+    }
+
+    /// Merges the completed candles of the timeframe below into the current candle
+    fn merge_all<T>(&mut self, candles: &[C])
+    where
+        C: ModularCandleMerge<T>,
+        T: TakerTrade,
+    {
+        for candle in candles {
+            if self.has_candles {
+                self.candle.merge(candle);
+            } else {
+                self.candle = candle.clone();
+                self.has_candles = true;
+            }
+        }
+    }
+
+    /// Takes the current candle, if any candle has been merged into it, and starts a new one
+    fn take_candle<T>(&mut self) -> Option<C>
+    where
+        C: ModularCandleMerge<T>,
+        T: TakerTrade,
+    {
+        if !self.has_candles {
+            return None;
+        }
+        let candle = self.candle.clone();
+        self.candle.reset();
+        self.has_candles = false;
+        Some(candle)
+    }
+
+    /// Evaluates the rule on a trade, given the candles of the timeframe below closed by it
+    fn update<T>(&mut self, trade: &T, candles: &[C]) -> Option<C>
+    where
+        C: ModularCandleMerge<T>,
+        R: AggregationRule<C, T>,
+        T: TakerTrade,
+    {
+        // The rule observes every trade, as stateful rules such as time rules depend on it
+        let close = self.rule.should_trigger(trade, &self.candle).is_close();
+        self.merge_all(candles);
+        if close {
+            return self.take_candle();
+        }
+        None
+    }
+
+    /// Evaluates the rule at `timestamp`, given the candles of the timeframe below closed by it
+    fn advance_time<T>(&mut self, timestamp: i64, candles: &[C]) -> Option<C>
+    where
+        C: ModularCandleMerge<T>,
+        R: AggregationRule<C, T>,
+        T: TakerTrade,
+    {
+        self.merge_all(candles);
+        if self.has_candles && self.rule.should_trigger_at(timestamp, &self.candle) {
+            return self.take_candle();
+        }
+        None
+    }
+
+    /// Takes the incomplete current candle, given the incomplete candles of the timeframe below
+    fn finish<T>(&mut self, candles: &[C]) -> Option<C>
+    where
+        C: ModularCandleMerge<T>,
+        R: AggregationRule<C, T>,
+        T: TakerTrade,
+    {
+        self.merge_all(candles);
+        let candle = self.take_candle();
+        if candle.is_some() {
+            self.rule.reset();
+        }
+        candle
+    }
+}
+
+/// Drives several aggregation rules over the same trades in one pass,
+/// e.g.: M1, M5 and H1 aligned time rules,
+/// and reports which timeframes closed a candle on each update.
+/// Only the base timeframe aggregates the trades,
+/// every higher timeframe merges the completed candles of the timeframe below it, see `ModularCandleMerge`,
+/// such that a candle is tied to the candles it is made of by `TimeframeCandle::trade_index`.
+/// The rule of a higher timeframe still observes every trade to decide when its candle closes,
+/// so every boundary of a timeframe is required to be a boundary of the timeframe below as well,
+/// e.g.: aligned time rules whose periods are multiples of each other.
+/// Otherwise a higher timeframe candle only covers the lower timeframe candles completed when it closes.
+/// Note that the trade at the boundary is counted twice when merging candles closed with `BoundaryPolicy::Both`,
+/// which doesn't apply to time rules, as they close a candle before the trade at the boundary.
+#[derive(Debug, Clone)]
+pub struct MultiTimeframeAggregator<C, R, T> {
+    base: GenericAggregator<C, R, T>,
+    timeframes: Vec<MergedTimeframe<C, R>>,
+
+    // The number of candles created per timeframe, including the base timeframe
+    sequences: Vec<u64>,
+
+    // The number of trades passed to the aggregator so far
+    num_trades: u64,
+
+    // Reused to pass the candles closed by one timeframe to the next one,
+    // avoiding an allocation per trade
+    buffer: Vec<C>,
+}
+
+impl<C, R, T> MultiTimeframeAggregator<C, R, T>
+where
+    C: ModularCandleMerge<T>,
+    R: AggregationRule<C, T>,
+    T: TakerTrade,
+{
+    /// Create a new instance with the base timeframe created by the given rule,
+    /// including the trade at the boundary in both candles
+    pub fn new(rule: R) -> Self {
+        Self::with_boundary_policy(rule, BoundaryPolicy::default())
+    }
+
+    /// Create a new instance with the base timeframe created by the given rule
+    ///
+    /// # Arguments:
+    /// rule: Decides when a candle of the base timeframe is finished
+    /// boundary_policy: Which candle of the base timeframe includes the trade at the boundary
+    ///
+    pub fn with_boundary_policy(rule: R, boundary_policy: BoundaryPolicy) -> Self {
+        Self {
+            base: GenericAggregator::with_boundary_policy(rule, boundary_policy),
+            timeframes: vec![],
+            sequences: vec![0],
+            num_trades: 0,
+            buffer: vec![],
+        }
+    }
+
+    /// Adds a timeframe built from the candles of the most recently added timeframe,
+    /// closing a candle whenever the given rule decides so
+    ///
+    /// # Returns:
+    /// The index of the timeframe, used in `TimeframeCandle::timeframe`
+    pub fn add_rule(&mut self, rule: R) -> usize {
+        self.timeframes.push(MergedTimeframe::new(rule));
+        self.sequences.push(0);
+        self.timeframes.len()
+    }
+
+    /// The number of timeframes, including the base timeframe
+    pub fn num_timeframes(&self) -> usize {
+        self.timeframes.len() + 1
+    }
+
+    /// Updates all timeframes with a new trade,
+    /// pushing all candles that have been created by it into `candles`
+    ///
+    /// # Arguments:
+    /// trade: the trade information to add to the aggregation process
+    /// candles: the created candles are appended to it, ordered by timeframe
+    pub fn update_into(&mut self, trade: &T, candles: &mut Vec<TimeframeCandle<C>>) {
+        let trade_index = self.num_trades;
+        self.num_trades += 1;
+        self.base.update_into(trade, &mut self.buffer);
+        self.cascade(trade_index, candles, |timeframe, lower| {
+            timeframe.update(trade, lower)
+        });
+    }
+
+    /// Informs all timeframes that time has advanced without a new trade,
+    /// see `Aggregator::advance_time`.
+    /// The created candles refer to the most recent trade in `TimeframeCandle::trade_index`.
+    ///
+    /// # Arguments:
+    /// timestamp: the current time, with the same resolution as the trade timestamps
+    /// candles: the created candles are appended to it, ordered by timeframe
+    pub fn advance_time_into(&mut self, timestamp: i64, candles: &mut Vec<TimeframeCandle<C>>) {
+        let trade_index = self.num_trades.saturating_sub(1);
+        self.base.advance_time_into(timestamp, &mut self.buffer);
+        self.cascade(trade_index, candles, |timeframe, lower| {
+            timeframe.advance_time(timestamp, lower)
+        });
+    }
+
+    /// Finishes the current aggregation period of all timeframes, see `Aggregator::finish`
+    ///
+    /// # Arguments:
+    /// candles: the incomplete candles are appended to it, ordered by timeframe
+    pub fn finish_into(&mut self, candles: &mut Vec<TimeframeCandle<C>>) {
+        let trade_index = self.num_trades.saturating_sub(1);
+        self.base.finish_into(&mut self.buffer);
+        self.cascade(trade_index, candles, |timeframe, lower| {
+            timeframe.finish(lower)
+        });
+    }
+
+    /// Passes the candles closed by each timeframe, starting with the base timeframe in `buffer`,
+    /// to `f` along with the timeframe above it, tagging them with their timeframe and sequence
+    fn cascade<F>(&mut self, trade_index: u64, candles: &mut Vec<TimeframeCandle<C>>, mut f: F)
+    where
+        F: FnMut(&mut MergedTimeframe<C, R>, &[C]) -> Option<C>,
+    {
+        for (timeframe, merged) in self.timeframes.iter_mut().enumerate() {
+            let closed = f(merged, &self.buffer);
+            tag_candles(
+                timeframe,
+                &mut self.sequences[timeframe],
+                trade_index,
+                &mut self.buffer,
+                candles,
+            );
+            self.buffer.extend(closed);
+        }
+        let timeframe = self.timeframes.len();
+        tag_candles(
+            timeframe,
+            &mut self.sequences[timeframe],
+            trade_index,
+            &mut self.buffer,
+            candles,
+        );
+    }
+}
+
+/// Moves the candles of a timeframe from `buffer` into `candles`,
+/// tagging them with their timeframe and sequence
+fn tag_candles<C>(
+    timeframe: usize,
+    sequence: &mut u64,
+    trade_index: u64,
+    buffer: &mut Vec<C>,
+    candles: &mut Vec<TimeframeCandle<C>>,
+) {
+    for candle in buffer.drain(..) {
+        candles.push(TimeframeCandle {
+            timeframe,
+            sequence: *sequence,
+            trade_index,
+            candle,
+        });
+        *sequence += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use trade_aggregation_derive::Candle;
+
+    use super::*;
+    use crate::{
+        aggregate_all_trades,
+        candle_components::{
+            CandleComponent, CandleComponentMerge, CandleComponentUpdate, Close, High, Low,
+            NumTrades, Open,
+        },
+        load_trades_from_csv, AlignedTimeRule, ModularCandle, TimestampResolution, Trade, H1, M1,
+        M5,
+    };
+
+    #[derive(Debug, Default, Clone, Candle)]
+    #[candle(merge)]
+    struct MyCandle {
+        open: Open,
+        high: High,
+        low: Low,
+        close: Close,
+        num_trades: NumTrades<u32>,
+    }
+
+    fn assert_same_candles(candles: &[&TimeframeCandle<MyCandle>], expected: &[MyCandle]) {
+        assert_eq!(candles.len(), expected.len());
+        for (i, (c, e)) in candles.iter().zip(expected.iter()).enumerate() {
+            assert_eq!(c.sequence, i as u64);
+            assert_eq!(c.candle.open(), e.open());
+            assert_eq!(c.candle.high(), e.high());
+            assert_eq!(c.candle.low(), e.low());
+            assert_eq!(c.candle.close(), e.close());
+            assert_eq!(c.candle.num_trades(), e.num_trades());
+        }
+    }
+
+    #[test]
+    fn multi_timeframe_aggregator() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+
+        let rule = |period| AlignedTimeRule::new(period, TimestampResolution::Millisecond);
+        let mut aggregator = MultiTimeframeAggregator::<MyCandle, _, Trade>::new(rule(M1));
+        let m5 = aggregator.add_rule(rule(M5));
+        let h1 = aggregator.add_rule(rule(H1));
+        assert_eq!(aggregator.num_timeframes(), 3);
+
+        let mut candles = vec![];
+        for t in &trades {
+            aggregator.update_into(t, &mut candles);
+        }
+
+        // Each timeframe creates the same candles as its own aggregator,
+        // even though only the base timeframe aggregates the trades
+        for (timeframe, period) in [(0, M1), (m5, M5), (h1, H1)] {
+            let mut single = GenericAggregator::<MyCandle, _, Trade>::new(rule(period));
+            let expected = aggregate_all_trades(&trades, &mut single);
+            let timeframe_candles: Vec<&TimeframeCandle<MyCandle>> = candles
+                .iter()
+                .filter(|c| c.timeframe == timeframe)
+                .collect();
+            assert_same_candles(&timeframe_candles, &expected);
+        }
+        assert_eq!(candles.iter().filter(|c| c.timeframe == 0).count(), 5953);
+
+        // Every H1 candle closes together with a M1 and a M5 candle,
+        // and is made of the M1 candles closed after the previous H1 candle, up to and including this one
+        let h1_candles: Vec<&TimeframeCandle<MyCandle>> =
+            candles.iter().filter(|c| c.timeframe == h1).collect();
+        let num_m1: Vec<usize> = h1_candles
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                for timeframe in [0, m5] {
+                    assert!(candles
+                        .iter()
+                        .any(|o| o.timeframe == timeframe && o.trade_index == c.trade_index));
+                }
+                candles
+                    .iter()
+                    .filter(|o| {
+                        o.timeframe == 0
+                            && o.trade_index <= c.trade_index
+                            && (i == 0 || o.trade_index > h1_candles[i - 1].trade_index)
+                    })
+                    .count()
+            })
+            .collect();
+        assert_eq!(h1_candles.len(), 99);
+        assert_eq!(num_m1, vec![60; 99]);
+
+        let mut partial = vec![];
+        aggregator.finish_into(&mut partial);
+        assert_eq!(partial.len(), 3);
+    }
+
+    #[test]
+    fn multi_timeframe_aggregator_advance_time() {
+        let rule = |period| AlignedTimeRule::new(period, TimestampResolution::Millisecond);
+        let mut aggregator = MultiTimeframeAggregator::<MyCandle, _, Trade>::new(rule(M1));
+        let m5 = aggregator.add_rule(rule(M5));
+
+        let mut candles = vec![];
+        for (timestamp, price) in [(0, 100.0), (30_000, 101.0), (70_000, 99.0)] {
+            aggregator.update_into(
+                &Trade {
+                    timestamp,
+                    price,
+                    size: 1.0,
+                },
+                &mut candles,
+            );
+        }
+        assert_eq!(candles.len(), 1);
+        assert_eq!(candles[0].candle.num_trades(), 2);
+
+        // Both the M1 candle of the last trade and the M5 candle merging all trades close
+        aggregator.advance_time_into(300_000, &mut candles);
+        assert_eq!(candles.len(), 3);
+        assert_eq!(candles[1].timeframe, 0);
+        assert_eq!(candles[1].sequence, 1);
+        assert_eq!(candles[1].candle.num_trades(), 1);
+        let c = &candles[2];
+        assert_eq!((c.timeframe, c.sequence, c.trade_index), (m5, 0, 2));
+        assert_eq!(c.candle.num_trades(), 3);
+        assert_eq!(c.candle.open(), 100.0);
+        assert_eq!(c.candle.high(), 101.0);
+        assert_eq!(c.candle.low(), 99.0);
+        assert_eq!(c.candle.close(), 99.0);
+
+        candles.clear();
+        aggregator.finish_into(&mut candles);
+        assert!(candles.is_empty());
+    }
+
+    #[test]
+    fn multi_timeframe_aggregator_send() {
+        let mut aggregator = MultiTimeframeAggregator::<MyCandle, _, Trade>::new(
+            AlignedTimeRule::new(M1, TimestampResolution::Millisecond),
+        );
+        aggregator.add_rule(AlignedTimeRule::new(M5, TimestampResolution::Millisecond));
+
+        let handle = std::thread::spawn(move || {
+            let mut candles = vec![];
+            aggregator.update_into(&Trade::default(), &mut candles);
+            aggregator.finish_into(&mut candles);
+            candles.len()
+        });
+        assert_eq!(handle.join().unwrap(), 2);
+    }
+}
@@ -0,0 +1,386 @@
+use std::ops::Range;
+
+use rayon::prelude::*;
+
+use crate::{
+    AggregationRule, BoundaryPolicy, CandleIndependentRule, ModularCandle, TakerTrade,
+    TriggerDecision,
+};
+
+/// Apply an aggregation rule for all trades at once, using all cores.
+/// As the rule does not depend on the content of the candles,
+/// the candle boundaries of each chunk of trades are located in parallel,
+/// starting with a copy of the given rule at the start of every chunk.
+/// Rules such as `TickRule`, `TimeRule` and `AlignedTimeRule` bring their copy into the state
+/// at the start of the chunk directly, see `CandleIndependentRule::skip_trades`,
+/// so the boundaries of every chunk are exact.
+/// The chunks are then joined in order: the rule is carried over from the previous chunk
+/// and applied to the trades of the next one until it closes a candle at the same trade as the copy did,
+/// after which both agree and the remaining boundaries of the chunk are kept.
+/// This joins the candle spanning the edge of two chunks,
+/// and for rules whose copy starts unchanged, confirms the boundaries of the chunk.
+/// Finally, the candles are built in parallel.
+///
+/// This creates the same candles as `aggregate_all_trades` using a `GenericAggregator`,
+/// and is meant for re-aggregating large amounts of historical trades, e.g.: for parameter sweeps.
+/// It assumes that the state of the rule after closing a candle only depends on the trade closing it,
+/// which holds for the rules of this crate.
+/// A rule starting unchanged at every chunk, such as `AnyOf`, may not agree with its copy for a while,
+/// in which case its boundaries are located sequentially.
+///
+/// # Arguments:
+/// trades: The input trade data to aggregate
+/// aggregation_rule: Decides when a candle is finished
+/// boundary_policy: Which candle includes the trade at the boundary
+///
+/// # Returns:
+/// A vector of aggregated candle data, not including the incomplete candle at the end of the trades
+pub fn aggregate_all_trades_parallel<C, R, T>(
+    trades: &[T],
+    aggregation_rule: R,
+    boundary_policy: BoundaryPolicy,
+) -> Vec<C>
+where
+    C: ModularCandle<T> + Send,
+    R: AggregationRule<C, T> + CandleIndependentRule + Clone + Send + Sync,
+    T: TakerTrade + Sync,
+{
+    candle_boundaries(trades, aggregation_rule, boundary_policy)
+        .into_par_iter()
+        .map(|range| {
+            let mut candle = C::default();
+            for trade in &trades[range] {
+                candle.update(trade);
+            }
+            candle
+        })
+        .collect()
+}
+
+/// A candle closed by the rule
+#[derive(Debug, Clone, PartialEq)]
+struct Boundary {
+    // The trades making up the candle
+    trades: Range<usize>,
+
+    // The index of the trade which made the rule close the candle
+    closed_by: usize,
+
+    // Whether the rule included that trade in the candle
+    including: bool,
+}
+
+/// Locates the candles within a chunk of trades
+#[derive(Debug)]
+struct Scan<R> {
+    rule: R,
+
+    // The index of the first trade of the current candle
+    start: usize,
+
+    boundaries: Vec<Boundary>,
+}
+
+impl<R> Scan<R> {
+    fn new(rule: R, start: usize) -> Self {
+        Self {
+            rule,
+            start,
+            boundaries: vec![],
+        }
+    }
+
+    /// Apply the rule to the trade at index `i`,
+    /// mirroring how the `GenericAggregator` handles the decisions of the rule
+    ///
+    /// # Returns:
+    /// Whether the rule closed a candle
+    fn step<C, T>(
+        &mut self,
+        i: usize,
+        trade: &T,
+        candle: &C,
+        boundary_policy: BoundaryPolicy,
+    ) -> bool
+    where
+        C: ModularCandle<T>,
+        R: AggregationRule<C, T>,
+        T: TakerTrade,
+    {
+        // The current candle contains the trades in `start..i`
+        let (including, end, next_start) = match self.rule.should_trigger(trade, candle) {
+            TriggerDecision::Continue => return false,
+            TriggerDecision::CloseBefore => (false, i, i),
+            // A trade is not split without knowing the content of the candle,
+            // so it is handled like the `GenericAggregator` handles a trade type not supporting it
+            TriggerDecision::CloseIncluding | TriggerDecision::CloseAndSplit(_) => {
+                match boundary_policy {
+                    BoundaryPolicy::Closing => (true, i + 1, i + 1),
+                    BoundaryPolicy::Next => (true, i, i),
+                    BoundaryPolicy::Both => (true, i + 1, i),
+                }
+            }
+        };
+        let closed = self.start < end;
+        if closed {
+            self.boundaries.push(Boundary {
+                trades: self.start..end,
+                closed_by: i,
+                including,
+            });
+        }
+        self.start = next_start;
+        closed
+    }
+}
+
+/// Locates the trades making up each complete candle,
+/// scanning chunks of trades in parallel and joining them in order, see `chunked_candle_boundaries`
+fn candle_boundaries<C, R, T>(
+    trades: &[T],
+    aggregation_rule: R,
+    boundary_policy: BoundaryPolicy,
+) -> Vec<Range<usize>>
+where
+    C: ModularCandle<T>,
+    R: AggregationRule<C, T> + CandleIndependentRule + Clone + Send + Sync,
+    T: TakerTrade + Sync,
+{
+    let chunk_size = trades.len().div_ceil(rayon::current_num_threads());
+    chunked_candle_boundaries(trades, aggregation_rule, boundary_policy, chunk_size).0
+}
+
+/// Locates the trades making up each complete candle,
+/// scanning chunks of `chunk_size` trades in parallel and joining them in order
+///
+/// # Returns:
+/// The trades of each candle, and the number of trades the rule was applied to again when joining
+fn chunked_candle_boundaries<C, R, T>(
+    trades: &[T],
+    aggregation_rule: R,
+    boundary_policy: BoundaryPolicy,
+    chunk_size: usize,
+) -> (Vec<Range<usize>>, usize)
+where
+    C: ModularCandle<T>,
+    R: AggregationRule<C, T> + CandleIndependentRule + Clone + Send + Sync,
+    T: TakerTrade + Sync,
+{
+    // The rule never looks at the candle, so an empty one suffices
+    let candle = C::default();
+
+    let chunk_size = chunk_size.max(1);
+    let mut scans: Vec<Scan<R>> = trades
+        .par_chunks(chunk_size)
+        .enumerate()
+        .map(|(n, chunk)| {
+            let offset = n * chunk_size;
+            let candle = C::default();
+            let mut rule = aggregation_rule.clone();
+            rule.skip_trades(&trades[..offset]);
+            let mut scan = Scan::new(rule, offset);
+            for (i, trade) in chunk.iter().enumerate() {
+                scan.step(offset + i, trade, &candle, boundary_policy);
+            }
+            scan
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+
+    // The first chunk starts with the given rule, so its boundaries are exact
+    let Some(mut joined) = scans.pop() else {
+        return (vec![], 0);
+    };
+    let mut offset = chunk_size;
+    let mut num_rescanned = 0;
+    while let Some(scan) = scans.pop() {
+        let chunk = &trades[offset..(offset + chunk_size).min(trades.len())];
+        let mut agreed = None;
+        for (i, trade) in chunk.iter().enumerate() {
+            num_rescanned += 1;
+            if !joined.step(offset + i, trade, &candle, boundary_policy) {
+                continue;
+            }
+            let last = joined.boundaries.last().expect("A candle has been closed");
+            let position = scan
+                .boundaries
+                .iter()
+                .position(|b| b.closed_by == last.closed_by && b.including == last.including);
+            if let Some(position) = position {
+                agreed = Some(position);
+                break;
+            }
+        }
+        if let Some(position) = agreed {
+            // From here on, the rule carried over behaves like the copy of the chunk
+            joined
+                .boundaries
+                .extend_from_slice(&scan.boundaries[position + 1..]);
+            joined.rule = scan.rule;
+            joined.start = scan.start;
+        }
+        offset += chunk_size;
+    }
+
+    let boundaries = joined.boundaries.into_iter().map(|b| b.trades).collect();
+    (boundaries, num_rescanned)
+}
+
+#[cfg(test)]
+mod tests {
+    use trade_aggregation_derive::Candle;
+
+    use super::*;
+    use crate::{
+        aggregate_all_trades,
+        candle_components::{
+            CandleComponent, CandleComponentUpdate, Close, High, Low, NumTrades, Open, Volume,
+        },
+        load_trades_from_csv, AlignedTimeRule, AnyOf, GenericAggregator, TickRule, TimeRule,
+        TimestampResolution, Trade, M1, M5,
+    };
+
+    #[derive(Debug, Default, Clone, Candle)]
+    struct MyCandle {
+        open: Open,
+        high: High,
+        low: Low,
+        close: Close,
+        volume: Volume,
+        num_trades: NumTrades<u32>,
+    }
+
+    fn assert_same_candles<R>(trades: &[Trade], rule: R)
+    where
+        R: AggregationRule<MyCandle, Trade> + CandleIndependentRule + Clone + Send + Sync,
+    {
+        for policy in [
+            BoundaryPolicy::Closing,
+            BoundaryPolicy::Next,
+            BoundaryPolicy::Both,
+        ] {
+            let mut aggregator =
+                GenericAggregator::<MyCandle, R, Trade>::with_boundary_policy(rule.clone(), policy);
+            let expected = aggregate_all_trades(trades, &mut aggregator);
+            let candles: Vec<MyCandle> =
+                aggregate_all_trades_parallel(trades, rule.clone(), policy);
+
+            assert_eq!(candles.len(), expected.len());
+            for (c, e) in candles.iter().zip(expected.iter()) {
+                assert_eq!(c.open(), e.open());
+                assert_eq!(c.high(), e.high());
+                assert_eq!(c.low(), e.low());
+                assert_eq!(c.close(), e.close());
+                assert_eq!(c.volume(), e.volume());
+                assert_eq!(c.num_trades(), e.num_trades());
+            }
+
+            // Joining many chunks, independent of the number of cores
+            for chunk_size in [1000, trades.len() / 3, trades.len()] {
+                let (boundaries, _) = chunked_candle_boundaries::<MyCandle, _, _>(
+                    trades,
+                    rule.clone(),
+                    policy,
+                    chunk_size,
+                );
+                assert_eq!(boundaries.len(), expected.len());
+                for (range, e) in boundaries.iter().zip(expected.iter()) {
+                    assert_eq!(range.len() as u32, e.num_trades());
+                    assert_eq!(trades[range.start].price, e.open());
+                    assert_eq!(trades[range.end - 1].price, e.close());
+                }
+            }
+
+            // Joining tiny chunks, where most candles span several of them
+            let prefix = &trades[..20_000];
+            let (expected, _) = chunked_candle_boundaries::<MyCandle, _, _>(
+                prefix,
+                rule.clone(),
+                policy,
+                prefix.len(),
+            );
+            for chunk_size in [1, 7] {
+                let (boundaries, _) = chunked_candle_boundaries::<MyCandle, _, _>(
+                    prefix,
+                    rule.clone(),
+                    policy,
+                    chunk_size,
+                );
+                assert_eq!(boundaries, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_aggregation() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+
+        assert_same_candles(&trades, TickRule::new(1000));
+        assert_same_candles(&trades, TimeRule::new(M1, TimestampResolution::Millisecond));
+        assert_same_candles(
+            &trades,
+            AlignedTimeRule::new(M5, TimestampResolution::Millisecond),
+        );
+        assert_same_candles(
+            &trades,
+            AnyOf::new(
+                TickRule::new(5000),
+                AlignedTimeRule::new(M5, TimestampResolution::Millisecond),
+            ),
+        );
+    }
+
+    #[test]
+    fn parallel_aggregation_skips_trades() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+        let chunk_size = 10_000;
+        let num_chunks = trades.len().div_ceil(chunk_size);
+
+        fn longest_candle<R>(trades: &[Trade], rule: R) -> usize
+        where
+            R: AggregationRule<MyCandle, Trade>,
+        {
+            let mut aggregator = GenericAggregator::<MyCandle, R, Trade>::with_boundary_policy(
+                rule,
+                BoundaryPolicy::Closing,
+            );
+            aggregate_all_trades(trades, &mut aggregator)
+                .iter()
+                .map(|c| c.num_trades() as usize)
+                .max()
+                .unwrap()
+        }
+
+        // The copy of the rule starts every chunk in the state the rule has there,
+        // so joining a chunk only applies the rule to the trades up to its first boundary
+        let rule = TickRule::new(1000);
+        let (_, num_rescanned) = chunked_candle_boundaries::<MyCandle, _, _>(
+            &trades,
+            rule.clone(),
+            BoundaryPolicy::Closing,
+            chunk_size,
+        );
+        assert!(num_rescanned <= (num_chunks - 1) * 1000);
+
+        let rule = TimeRule::new(M1, TimestampResolution::Millisecond);
+        let (_, num_rescanned) = chunked_candle_boundaries::<MyCandle, _, _>(
+            &trades,
+            rule.clone(),
+            BoundaryPolicy::Closing,
+            chunk_size,
+        );
+        assert!(num_rescanned <= (num_chunks - 1) * (longest_candle(&trades, rule) + 1));
+
+        let rule = AlignedTimeRule::new(M5, TimestampResolution::Millisecond);
+        let (_, num_rescanned) = chunked_candle_boundaries::<MyCandle, _, _>(
+            &trades,
+            rule.clone(),
+            BoundaryPolicy::Closing,
+            chunk_size,
+        );
+        assert!(num_rescanned <= (num_chunks - 1) * (longest_candle(&trades, rule) + 1));
+        assert!(num_rescanned < trades.len() / 10);
+    }
+}
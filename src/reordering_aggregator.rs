@@ -0,0 +1,465 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, VecDeque},
+    marker::PhantomData,
+};
+
+use crate::{Aggregator, Error, ModularCandle, Result, TakerTrade};
+
+/// A trade waiting in the reorder buffer
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct BufferedTrade<T> {
+    timestamp: i64,
+
+    // The number of trades that arrived before this one, keeping the order of equal timestamps
+    arrival: u64,
+
+    trade: T,
+}
+
+impl<T> BufferedTrade<T> {
+    fn key(&self) -> (i64, u64) {
+        (self.timestamp, self.arrival)
+    }
+}
+
+impl<T> PartialEq for BufferedTrade<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<T> Eq for BufferedTrade<T> {}
+
+impl<T> PartialOrd for BufferedTrade<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for BufferedTrade<T> {
+    // Reversed, such that the `BinaryHeap` yields the earliest trade first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key().cmp(&self.key())
+    }
+}
+
+/// Tolerates trades arriving slightly out of order,
+/// e.g.: when merging the feeds of multiple websocket connections,
+/// by buffering them and releasing them to the inner aggregator in timestamp order.
+/// A trade is released once a trade at least `max_lateness` later has arrived.
+/// Trades arriving after a later trade has already been released,
+/// or older than the watermark `advance_time` moved to, can't be put in order anymore,
+/// so they are dropped and counted, see `num_late_trades`.
+/// Buffering requires the trade type to implement `Clone`.
+///
+/// As releasing a trade can release several buffered trades at once, which may create multiple candles,
+/// use `update_into`, `advance_time_into` and `finish_into` to receive all of them.
+/// `update`, `advance_time` and `finish` return one candle per call,
+/// keeping the others for the following calls.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReorderingAggregator<A, C, T> {
+    aggregator: A,
+
+    // How much later than the most recent timestamp a trade may arrive
+    max_lateness: i64,
+
+    buffer: BinaryHeap<BufferedTrade<T>>,
+
+    // The number of trades that arrived so far
+    num_arrived: u64,
+
+    // The most recent timestamp observed, of a trade or the wall clock
+    max_timestamp: i64,
+
+    // The timestamp of the most recently released trade or the watermark the clock advanced to,
+    // before which arriving trades are late
+    released_timestamp: Option<i64>,
+
+    num_late_trades: u64,
+
+    // The candles created, but not yet returned by `update`, `advance_time` or `finish`
+    pending: VecDeque<C>,
+
+    candle_type: PhantomData<C>,
+}
+
+impl<A, C, T> ReorderingAggregator<A, C, T>
+where
+    A: Aggregator<C, T>,
+    C: ModularCandle<T>,
+    T: TakerTrade,
+{
+    /// Create a new instance, releasing the trades to `aggregator` in timestamp order
+    ///
+    /// # Arguments:
+    /// aggregator: The inner aggregator receiving the trades in timestamp order
+    /// max_lateness: How much older than the most recent trade a trade may be when it arrives,
+    /// with the same resolution as the trade timestamps. Must not be negative.
+    ///
+    pub fn new(aggregator: A, max_lateness: i64) -> Result<Self> {
+        if max_lateness < 0 {
+            return Err(Error::InvalidParam);
+        }
+        Ok(Self {
+            aggregator,
+            max_lateness,
+            buffer: BinaryHeap::new(),
+            num_arrived: 0,
+            max_timestamp: i64::MIN,
+            released_timestamp: None,
+            num_late_trades: 0,
+            pending: VecDeque::new(),
+            candle_type: PhantomData,
+        })
+    }
+
+    /// The number of trades which arrived too late and have been dropped
+    pub fn num_late_trades(&self) -> u64 {
+        self.num_late_trades
+    }
+
+    /// The number of trades waiting to be released to the inner aggregator
+    pub fn num_buffered_trades(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Releases all buffered trades up to and including the `watermark` timestamp
+    fn release(&mut self, watermark: i64, candles: &mut Vec<C>) {
+        while self
+            .buffer
+            .peek()
+            .is_some_and(|buffered| buffered.timestamp <= watermark)
+        {
+            let buffered = self.buffer.pop().expect("A trade has been peeked");
+            self.released_timestamp = Some(buffered.timestamp);
+            self.aggregator.update_into(&buffered.trade, candles);
+        }
+    }
+
+    /// The timestamp up to which all trades are released
+    fn watermark(&self) -> i64 {
+        self.max_timestamp.saturating_sub(self.max_lateness)
+    }
+
+    /// Buffer a trade, pushing the candles created by releasing trades into `candles`
+    fn process(&mut self, trade: &T, candles: &mut Vec<C>)
+    where
+        T: Clone,
+    {
+        let timestamp = trade.timestamp();
+        if self
+            .released_timestamp
+            .is_some_and(|released| timestamp < released)
+        {
+            self.num_late_trades += 1;
+            return;
+        }
+        self.buffer.push(BufferedTrade {
+            timestamp,
+            arrival: self.num_arrived,
+            trade: trade.clone(),
+        });
+        self.num_arrived += 1;
+        self.max_timestamp = self.max_timestamp.max(timestamp);
+        self.release(self.watermark(), candles);
+    }
+
+    /// Advance the time, pushing the candles created by releasing trades
+    /// and the candle closed by the inner aggregator into `candles`
+    fn process_time(&mut self, timestamp: i64, candles: &mut Vec<C>) {
+        self.max_timestamp = self.max_timestamp.max(timestamp);
+        let watermark = self.watermark();
+        self.release(watermark, candles);
+        self.aggregator.advance_time_into(watermark, candles);
+        // The inner aggregator may have closed a candle up to the watermark,
+        // so an earlier trade can't be put in order anymore
+        self.released_timestamp = Some(
+            self.released_timestamp
+                .map_or(watermark, |released| released.max(watermark)),
+        );
+    }
+
+    /// The oldest pending candle, after adding the given candles to the pending ones
+    fn next_pending(&mut self, candles: Vec<C>) -> Option<C> {
+        self.pending.extend(candles);
+        self.pending.pop_front()
+    }
+}
+
+impl<A, C, T> Aggregator<C, T> for ReorderingAggregator<A, C, T>
+where
+    A: Aggregator<C, T>,
+    C: ModularCandle<T>,
+    T: TakerTrade + Clone,
+{
+    fn update(&mut self, trade: &T) -> Option<C> {
+        let mut candles = vec![];
+        self.process(trade, &mut candles);
+        self.next_pending(candles)
+    }
+
+    fn update_into(&mut self, trade: &T, candles: &mut Vec<C>) {
+        candles.extend(self.pending.drain(..));
+        self.process(trade, candles);
+    }
+
+    fn advance_time(&mut self, timestamp: i64) -> Option<C> {
+        let mut candles = vec![];
+        self.process_time(timestamp, &mut candles);
+        self.next_pending(candles)
+    }
+
+    /// Releases the trades that can no longer be preceded by a late trade,
+    /// and advances the inner aggregator up to them,
+    /// such that candles are only closed once their late trades had the chance to arrive
+    fn advance_time_into(&mut self, timestamp: i64, candles: &mut Vec<C>) {
+        candles.extend(self.pending.drain(..));
+        self.process_time(timestamp, candles);
+    }
+
+    /// Releases all buffered trades, as if no trade arrives late anymore
+    fn flush_into(&mut self, candles: &mut Vec<C>) {
+        candles.extend(self.pending.drain(..));
+        self.release(i64::MAX, candles);
+        self.aggregator.flush_into(candles);
+    }
+
+    /// Releases all buffered trades and finishes the inner aggregator
+    fn finish(&mut self) -> Option<C> {
+        let mut candles = vec![];
+        self.release(i64::MAX, &mut candles);
+        self.aggregator.finish_into(&mut candles);
+        self.next_pending(candles)
+    }
+
+    fn finish_into(&mut self, candles: &mut Vec<C>) {
+        candles.extend(self.pending.drain(..));
+        self.release(i64::MAX, candles);
+        self.aggregator.finish_into(candles);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use trade_aggregation_derive::Candle;
+
+    use super::*;
+    use crate::{
+        aggregate_all_trades_with_partial,
+        candle_components::{CandleComponent, CandleComponentUpdate, High, Low, NumTrades},
+        load_trades_from_csv, BoundaryPolicy, GenericAggregator, TickRule, TimeRule,
+        TimestampResolution, Trade, M1,
+    };
+
+    #[derive(Debug, Default, Clone, Candle)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    struct MyCandle {
+        high: High,
+        low: Low,
+        num_trades: NumTrades<u32>,
+    }
+
+    #[test]
+    fn reordering_aggregator_invalid_param() {
+        let aggregator = GenericAggregator::<MyCandle, _, Trade>::new(TimeRule::new(
+            M1,
+            TimestampResolution::Millisecond,
+        ));
+        assert!(ReorderingAggregator::new(aggregator, -1).is_err());
+    }
+
+    #[test]
+    fn reordering_aggregator() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+        let rule = TimeRule::new(M1, TimestampResolution::Millisecond);
+
+        let mut aggregator = GenericAggregator::<MyCandle, _, Trade>::new(rule.clone());
+        let (mut expected, partial) = aggregate_all_trades_with_partial(&trades, &mut aggregator);
+        expected.extend(partial);
+
+        // Interleave the trades out of order, by reversing every chunk of 5 trades
+        let mut shuffled = trades.clone();
+        let mut max_lateness = 0;
+        for chunk in shuffled.chunks_mut(5) {
+            chunk.reverse();
+            max_lateness = max_lateness.max(chunk[0].timestamp - chunk[chunk.len() - 1].timestamp);
+        }
+
+        let inner = GenericAggregator::<MyCandle, _, Trade>::new(rule);
+        let mut aggregator = ReorderingAggregator::new(inner, max_lateness).unwrap();
+        // The most recent trades remain buffered until finishing
+        let (mut candles, partial) = aggregate_all_trades_with_partial(&shuffled, &mut aggregator);
+        candles.extend(partial);
+        assert_eq!(aggregator.num_late_trades(), 0);
+
+        assert_eq!(candles.len(), expected.len());
+        for (c, e) in candles.iter().zip(expected.iter()) {
+            assert_eq!(c.high(), e.high());
+            assert_eq!(c.low(), e.low());
+            assert_eq!(c.num_trades(), e.num_trades());
+        }
+    }
+
+    fn trade(timestamp: i64) -> Trade {
+        Trade {
+            timestamp,
+            price: timestamp as f64,
+            size: 1.0,
+        }
+    }
+
+    #[test]
+    fn reordering_aggregator_late_trades() {
+        let rule = TimeRule::new(100, TimestampResolution::Second);
+        let inner = GenericAggregator::<MyCandle, _, Trade>::with_boundary_policy(
+            rule,
+            BoundaryPolicy::Closing,
+        );
+        let mut aggregator = ReorderingAggregator::new(inner, 10).unwrap();
+
+        let mut candles = vec![];
+        aggregator.update_into(&trade(100), &mut candles);
+        aggregator.update_into(&trade(95), &mut candles);
+        assert_eq!(aggregator.num_buffered_trades(), 2);
+        // Releases the trades up to 110
+        aggregator.update_into(&trade(120), &mut candles);
+        assert_eq!(aggregator.num_buffered_trades(), 1);
+        // Arrives after the trade at 100 has been released
+        aggregator.update_into(&trade(90), &mut candles);
+        assert_eq!(aggregator.num_late_trades(), 1);
+
+        // The clock releases the trade at 120 and closes the candle
+        aggregator.advance_time_into(210, &mut candles);
+        assert_eq!(candles.len(), 1);
+        assert_eq!(candles[0].low(), 95.0);
+        assert_eq!(candles[0].high(), 120.0);
+        assert_eq!(candles[0].num_trades(), 3);
+
+        aggregator.update_into(&trade(250), &mut candles);
+        aggregator.finish_into(&mut candles);
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[1].num_trades(), 1);
+    }
+
+    #[test]
+    fn reordering_aggregator_late_after_clock() {
+        let rule = TimeRule::new(M1, TimestampResolution::Millisecond);
+        let inner = GenericAggregator::<MyCandle, _, Trade>::new(rule);
+        let mut aggregator = ReorderingAggregator::new(inner, 10_000).unwrap();
+
+        let mut candles = vec![];
+        aggregator.update_into(&trade(0), &mut candles);
+        aggregator.update_into(&trade(30_000), &mut candles);
+        // The watermark of 65_000 closes the candle of the first minute
+        aggregator.advance_time_into(75_000, &mut candles);
+        assert_eq!(candles.len(), 1);
+        assert_eq!(candles[0].num_trades(), 2);
+
+        // Would belong to the second minute, but arrives after the clock passed it
+        aggregator.update_into(&trade(55_000), &mut candles);
+        aggregator.update_into(&trade(64_999), &mut candles);
+        assert_eq!(aggregator.num_late_trades(), 2);
+        assert_eq!(aggregator.num_buffered_trades(), 0);
+
+        aggregator.update_into(&trade(65_000), &mut candles);
+        assert_eq!(aggregator.num_late_trades(), 2);
+        aggregator.finish_into(&mut candles);
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[1].low(), 65_000.0);
+        assert_eq!(candles[1].num_trades(), 1);
+    }
+
+    #[test]
+    fn reordering_aggregator_update() {
+        let inner = GenericAggregator::<MyCandle, _, Trade>::with_boundary_policy(
+            TickRule::new(1),
+            BoundaryPolicy::Closing,
+        );
+        let mut aggregator = ReorderingAggregator::new(inner, 10).unwrap();
+
+        assert!(aggregator.update(&trade(2)).is_none());
+        assert!(aggregator.update(&trade(0)).is_none());
+        assert!(aggregator.update(&trade(1)).is_none());
+        // Releases the three buffered trades, each closing a candle
+        let candle = aggregator.update(&trade(20)).unwrap();
+        assert_eq!(candle.high(), 0.0);
+
+        // The other candles follow in order, before the one of the released trade at 20
+        let mut highs = vec![];
+        while let Some(candle) = aggregator.finish() {
+            highs.push(candle.high());
+        }
+        assert_eq!(highs, vec![1.0, 2.0, 20.0]);
+    }
+
+    #[test]
+    fn reordering_aggregator_no_partial() {
+        let inner = GenericAggregator::<MyCandle, _, Trade>::with_boundary_policy(
+            TickRule::new(2),
+            BoundaryPolicy::Closing,
+        );
+        let mut aggregator = ReorderingAggregator::new(inner, 10).unwrap();
+
+        // Both trades remain buffered until finishing, which completes a candle without a partial one
+        let (candles, partial) =
+            aggregate_all_trades_with_partial(&[trade(1), trade(0)], &mut aggregator);
+        assert_eq!(candles.len(), 1);
+        assert_eq!(candles[0].num_trades(), 2);
+        assert!(partial.is_none());
+
+        // A trade after the completed candle is the partial one
+        let (candles, partial) =
+            aggregate_all_trades_with_partial(&[trade(3), trade(2), trade(4)], &mut aggregator);
+        assert_eq!(candles.len(), 1);
+        assert_eq!(candles[0].low(), 2.0);
+        assert_eq!(candles[0].high(), 3.0);
+        assert_eq!(partial.map(|c| c.num_trades()), Some(1));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn reordering_aggregator_serde() {
+        use crate::aggregate_all_trades;
+
+        type MyAggregator =
+            ReorderingAggregator<GenericAggregator<MyCandle, TimeRule, Trade>, MyCandle, Trade>;
+
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+        // Reverse every chunk of 5 trades, such that some are late and others remain buffered
+        let mut shuffled = trades[..100_000].to_vec();
+        for chunk in shuffled.chunks_mut(5) {
+            chunk.reverse();
+        }
+        let (first, second) = shuffled.split_at(shuffled.len() / 2 + 3);
+        let new_aggregator = || {
+            let rule = TimeRule::new(M1, TimestampResolution::Millisecond);
+            let inner = GenericAggregator::<MyCandle, _, Trade>::new(rule);
+            ReorderingAggregator::new(inner, 100).unwrap()
+        };
+
+        let mut aggregator = new_aggregator();
+        let expected = aggregate_all_trades(&shuffled, &mut aggregator);
+        let num_late_trades = aggregator.num_late_trades();
+        assert!(num_late_trades > 0);
+
+        // Snapshot the aggregator while it buffers trades and resume from the snapshot
+        let mut aggregator = new_aggregator();
+        let mut candles = aggregate_all_trades(first, &mut aggregator);
+        assert!(!aggregator.buffer.is_empty());
+        let snapshot = serde_json::to_string(&aggregator).unwrap();
+
+        let mut aggregator: MyAggregator = serde_json::from_str(&snapshot).unwrap();
+        candles.append(&mut aggregate_all_trades(second, &mut aggregator));
+        assert_eq!(aggregator.num_late_trades(), num_late_trades);
+
+        assert_eq!(candles.len(), expected.len());
+        for (c, e) in candles.iter().zip(expected.iter()) {
+            assert_eq!(c.high(), e.high());
+            assert_eq!(c.low(), e.low());
+            assert_eq!(c.num_trades(), e.num_trades());
+        }
+    }
+}
@@ -30,6 +30,63 @@ impl TakerTrade for Trade {
     fn size(&self) -> f64 {
         self.size
     }
+
+    #[inline(always)]
+    fn with_size(&self, size: f64) -> Option<Self> {
+        Some(Self { size, ..*self })
+    }
+}
+
+/// A taker trade along with the unique id assigned by the exchange, if known,
+/// e.g.: as read by `TradeCsvReader::with_trade_ids`.
+/// The id is returned by `TakerTrade::trade_id`, e.g.: to ignore replayed trades using a `DeduplicatingAggregator`.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IdentifiedTrade {
+    /// The trade itself
+    pub trade: Trade,
+
+    /// The id of the trade, if known
+    pub trade_id: Option<u64>,
+}
+
+impl From<Trade> for IdentifiedTrade {
+    fn from(trade: Trade) -> Self {
+        Self {
+            trade,
+            trade_id: None,
+        }
+    }
+}
+
+impl TakerTrade for IdentifiedTrade {
+    #[inline(always)]
+    fn timestamp(&self) -> i64 {
+        self.trade.timestamp
+    }
+
+    #[inline(always)]
+    fn price(&self) -> f64 {
+        self.trade.price
+    }
+
+    #[inline(always)]
+    fn size(&self) -> f64 {
+        self.trade.size
+    }
+
+    #[inline(always)]
+    fn with_size(&self, size: f64) -> Option<Self> {
+        Some(Self {
+            trade: Trade { size, ..self.trade },
+            ..*self
+        })
+    }
+
+    #[inline(always)]
+    fn trade_id(&self) -> Option<u64> {
+        self.trade_id
+    }
 }
 
 /// Defines how to aggregate trade size
@@ -46,6 +103,41 @@ pub enum By {
     Quote,
 }
 
+impl By {
+    /// The absolute volume of a trade, denoted in the units defined by `self`
+    #[inline(always)]
+    pub(crate) fn volume<T: TakerTrade>(&self, trade: &T) -> f64 {
+        match self {
+            By::Quote => trade.size().abs(),
+            By::Base => trade.size().abs() / trade.price(),
+        }
+    }
+}
+
+/// Defines how the notional value of a trade is computed,
+/// depending on the denomination of the trade size
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ContractType {
+    /// Trade size is denoted in Base currency, e.g.: buying 1 BTC on BTCUSDT,
+    /// so the notional value is `size * price`, denoted in Quote
+    Linear,
+    /// Trade size is denoted in Quote currency, e.g.: buying 100 contracts of XBTUSD on Bitmex,
+    /// so the notional value is `size / price`, denoted in Base
+    Inverse,
+}
+
+impl ContractType {
+    /// The absolute notional value of a trade
+    #[inline(always)]
+    pub(crate) fn notional<T: TakerTrade>(&self, trade: &T) -> f64 {
+        match self {
+            ContractType::Linear => trade.size().abs() * trade.price(),
+            ContractType::Inverse => trade.size().abs() / trade.price(),
+        }
+    }
+}
+
 /// Trait to enable third party types to be passed into aggregators.
 pub trait TakerTrade {
     /// The timestamp of a trade,
@@ -64,4 +156,21 @@ pub trait TakerTrade {
     /// A negative value indicates
     /// that the trade was executed on the bid (market sell order).
     fn size(&self) -> f64;
+
+    /// A copy of this trade with a different size,
+    /// used to split a trade across two candles, see `TriggerDecision::CloseAndSplit`.
+    /// The default returns None, in which case the trade is not split.
+    fn with_size(&self, _size: f64) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// The unique id of the trade assigned by the exchange, if any,
+    /// used to ignore trades that have already been seen, see `DeduplicatingAggregator`.
+    /// The default returns None, in which case the trade is never considered a duplicate.
+    fn trade_id(&self) -> Option<u64> {
+        None
+    }
 }
@@ -1,6 +1,12 @@
-use std::fs::File;
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+};
 
-use crate::{errors::Result, Aggregator, ModularCandle, TakerTrade, Trade};
+use crate::{
+    errors::{Error, Result},
+    Aggregator, CsvSchema, IdentifiedTrade, ModularCandle, TakerTrade, Trade,
+};
 
 /// Determine the candle volume which produces the same number of candles
 /// as the given time aggregation equivalent
@@ -43,14 +49,86 @@ where
     let mut out: Vec<C> = vec![];
 
     for t in trades {
-        if let Some(candle) = aggregator.update(t) {
-            out.push(candle)
-        }
+        aggregator.update_into(t, &mut out);
     }
 
     out
 }
 
+/// Apply an aggregator for all trades at once,
+/// also finishing the incomplete candle at the end of the trades
+///
+/// # Arguments:
+/// trades: The input trade data to aggregate
+/// aggregator: Something that can aggregate
+///
+/// # Returns:
+/// A vector of aggregated candle data,
+/// and the incomplete trailing candle if there are any trades after the last complete candle
+pub fn aggregate_all_trades_with_partial<A, C, T>(
+    trades: &[T],
+    aggregator: &mut A,
+) -> (Vec<C>, Option<C>)
+where
+    A: Aggregator<C, T>,
+    C: ModularCandle<T>,
+    T: TakerTrade,
+{
+    let mut out = aggregate_all_trades(trades, aggregator);
+    // Finishing may complete further candles before the incomplete one
+    aggregator.flush_into(&mut out);
+    let partial = aggregator.finish();
+
+    (out, partial)
+}
+
+/// A reader of a trade file, which may be decompressing it, see `open_trade_file`
+pub type TradeFileReader = Box<dyn Read + Send>;
+
+/// Open a trade file, transparently decompressing gzip and zstd compressed files,
+/// such as `.csv.gz` and `.csv.zst` archives, detected by their magic bytes.
+/// Decompression requires the gzip or zstd feature respectively.
+///
+/// # Arguments:
+/// filename: The path to the file
+///
+/// # Returns:
+/// If Ok, a reader of the decompressed content of the file
+pub fn open_trade_file(filename: &str) -> Result<TradeFileReader> {
+    let mut file = File::open(filename)?;
+    let mut magic = Vec::with_capacity(4);
+    (&mut file).take(4).read_to_end(&mut magic)?;
+    file.seek(SeekFrom::Start(0))?;
+
+    let file = BufReader::new(file);
+    match magic.as_slice() {
+        [0x1f, 0x8b, ..] => gzip_decoder(file),
+        [0x28, 0xb5, 0x2f, 0xfd] => zstd_decoder(file),
+        _ => Ok(Box::new(file)),
+    }
+}
+
+#[cfg(feature = "gzip")]
+fn gzip_decoder(file: BufReader<File>) -> Result<TradeFileReader> {
+    // Archives may consist of multiple concatenated gzip members
+    Ok(Box::new(flate2::read::MultiGzDecoder::new(file)))
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gzip_decoder(_file: BufReader<File>) -> Result<TradeFileReader> {
+    Err(Error::UnsupportedCompression("gzip"))
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_decoder(file: BufReader<File>) -> Result<TradeFileReader> {
+    Ok(Box::new(zstd::Decoder::with_buffer(file)?))
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_decoder(_file: BufReader<File>) -> Result<TradeFileReader> {
+    Err(Error::UnsupportedCompression("zstd"))
+}
+
 /// Load trades from csv file
 ///
 /// # Arguments:
@@ -59,28 +137,172 @@ where
 /// # Returns
 /// If Ok, A vector of the trades inside the file
 pub fn load_trades_from_csv(filename: &str) -> Result<Vec<Trade>> {
-    let f = File::open(filename)?;
+    TradeCsvReader::from_path(filename)?.collect()
+}
+
+/// Load trades from a csv file laid out as described by `schema`
+///
+/// # Arguments:
+/// filename: The path to the csv file
+/// schema: The layout of the csv file
+///
+/// # Returns
+/// If Ok, A vector of the trades inside the file
+pub fn load_trades_from_csv_with_schema(filename: &str, schema: CsvSchema) -> Result<Vec<Trade>> {
+    TradeCsvReader::from_path_with_schema(filename, schema)?.collect()
+}
+
+/// Reads trades from csv data one at a time, instead of loading all of them into memory,
+/// e.g.: from a file, stdin or a decompressor.
+/// By default, the csv data has a header, followed by the timestamp, price and size of each trade,
+/// as in the files read by `load_trades_from_csv`. Other layouts are described by a `CsvSchema`.
+/// Combine it with `AggregateExt` to aggregate files that don't fit into memory.
+#[derive(Debug)]
+pub struct TradeCsvReader<R> {
+    reader: csv::Reader<R>,
+    schema: CsvSchema,
 
-    let mut r = csv::Reader::from_reader(f);
+    // The resolved indices of the timestamp, price, size, side and trade id columns
+    columns: [usize; 3],
+    side: Option<usize>,
+    trade_id: Option<usize>,
 
-    let mut out: Vec<Trade> = vec![];
-    for record in r.records() {
-        let row = record?;
+    // Reused for every row, avoiding an allocation per trade
+    record: csv::StringRecord,
+}
+
+impl<R: Read> TradeCsvReader<R> {
+    /// Create a new instance reading the csv data from `reader`, using the default `CsvSchema`
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: csv::Reader::from_reader(reader),
+            schema: CsvSchema::default(),
+            columns: [0, 1, 2],
+            side: None,
+            trade_id: None,
+            record: csv::StringRecord::new(),
+        }
+    }
 
-        let ts = row[0].parse::<i64>()?;
-        let price = row[1].parse::<f64>()?;
-        let size = row[2].parse::<f64>()?;
+    /// Create a new instance reading the csv data from `reader`, laid out as described by `schema`
+    ///
+    /// # Returns:
+    /// An error if a named column does not exist in the header row
+    pub fn with_schema(reader: R, schema: CsvSchema) -> Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(schema.delimiter)
+            .has_headers(schema.has_headers)
+            .from_reader(reader);
+        let headers = if schema.has_headers {
+            Some(reader.headers()?.clone())
+        } else {
+            None
+        };
+        let columns = [
+            schema.timestamp.resolve(headers.as_ref())?,
+            schema.price.resolve(headers.as_ref())?,
+            schema.size.resolve(headers.as_ref())?,
+        ];
+        let side = schema
+            .side
+            .as_ref()
+            .map(|side| side.resolve(headers.as_ref()))
+            .transpose()?;
+        let trade_id = schema
+            .trade_id
+            .as_ref()
+            .map(|trade_id| trade_id.resolve(headers.as_ref()))
+            .transpose()?;
 
-        // convert to Trade
-        let trade = Trade {
+        Ok(Self {
+            reader,
+            schema,
+            columns,
+            side,
+            trade_id,
+            record: csv::StringRecord::new(),
+        })
+    }
+
+    /// The field of the current row in the given column
+    fn field(&self, column: usize) -> Result<&str> {
+        self.record
+            .get(column)
+            .ok_or_else(|| Error::MissingColumn(column.to_string()))
+    }
+
+    /// Parse the current row into a trade
+    fn parse_record(&self) -> Result<Trade> {
+        let [timestamp, price, size] = self.columns;
+
+        let ts = self.schema.parse_timestamp(self.field(timestamp)?)?;
+        let price = self.field(price)?.parse::<f64>()?;
+        let mut size = self.field(size)?.parse::<f64>()?;
+        if let Some(side) = self.side {
+            size = self.schema.signed_size(self.field(side)?, size)?;
+        }
+
+        Ok(Trade {
             timestamp: ts,
             price,
             size,
-        };
-        out.push(trade);
+        })
+    }
+
+    /// Parse the trade id of the current row, if the schema has a trade id column
+    fn parse_trade_id(&self) -> Result<Option<u64>> {
+        self.trade_id
+            .map(|trade_id| Ok(self.field(trade_id)?.parse::<u64>()?))
+            .transpose()
+    }
+
+    /// Read the next row into `record`
+    ///
+    /// # Returns:
+    /// None at the end of the csv data
+    fn read_record(&mut self) -> Option<Result<()>> {
+        match self.reader.read_record(&mut self.record) {
+            Ok(true) => Some(Ok(())),
+            Ok(false) => None,
+            Err(e) => Some(Err(e.into())),
+        }
     }
 
-    Ok(out)
+    /// Turns the reader into an iterator of trades along with their ids,
+    /// read from the trade id column of the schema, see `CsvSchema::with_trade_id_column`.
+    /// Without such a column, the ids are None.
+    pub fn with_trade_ids(mut self) -> impl Iterator<Item = Result<IdentifiedTrade>> {
+        std::iter::from_fn(move || {
+            Some(self.read_record()?.and_then(|()| {
+                Ok(IdentifiedTrade {
+                    trade: self.parse_record()?,
+                    trade_id: self.parse_trade_id()?,
+                })
+            }))
+        })
+    }
+}
+
+impl TradeCsvReader<TradeFileReader> {
+    /// Create a new instance reading the csv file at the given path,
+    /// which may be compressed, see `open_trade_file`
+    pub fn from_path(filename: &str) -> Result<Self> {
+        Ok(Self::new(open_trade_file(filename)?))
+    }
+
+    /// Create a new instance reading the csv file at the given path, laid out as described by `schema`,
+    /// which may be compressed, see `open_trade_file`
+    pub fn from_path_with_schema(filename: &str, schema: CsvSchema) -> Result<Self> {
+        Self::with_schema(open_trade_file(filename)?, schema)
+    }
+}
+
+impl<R: Read> Iterator for TradeCsvReader<R> {
+    type Item = Result<Trade>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.read_record()?.and_then(|()| self.parse_record()))
+    }
 }
 
 #[cfg(test)]
@@ -88,6 +310,10 @@ mod tests {
     use round::round;
 
     use super::*;
+    use crate::{
+        formats::TradeFileFormat, plot::OhlcCandle, AggregateExt, GenericAggregator, TickRule,
+        TimestampFormat, TimestampResolution,
+    };
 
     // TODO: re-enable this test
     /*
@@ -100,6 +326,125 @@ mod tests {
     }
     */
 
+    #[test]
+    fn test_aggregate_all_trades_with_partial() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+        let mut aggregator = GenericAggregator::<OhlcCandle, _, Trade>::new(TickRule::new(1000));
+        let (candles, partial) = aggregate_all_trades_with_partial(&trades, &mut aggregator);
+        assert_eq!(candles.len(), trades.len() / 1000);
+
+        // The trades after the last complete candle end up in the incomplete candle
+        let partial = partial.unwrap();
+        assert_eq!(partial.close(), trades.last().unwrap().price);
+    }
+
+    #[test]
+    fn test_trade_csv_reader() {
+        let data = "timestamp,price,size\n1000,100.5,10\n2000,101.0,-5\n";
+        let trades: Vec<Trade> = TradeCsvReader::new(data.as_bytes())
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            trades,
+            vec![
+                Trade {
+                    timestamp: 1000,
+                    price: 100.5,
+                    size: 10.0,
+                },
+                Trade {
+                    timestamp: 2000,
+                    price: 101.0,
+                    size: -5.0,
+                },
+            ]
+        );
+
+        let data = "timestamp,price,size\n1000,abc,10\n";
+        let mut reader = TradeCsvReader::new(data.as_bytes());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_trade_csv_reader_with_schema() {
+        let data = "side;qty;time;px\nSell;5;2018-01-01D00:00:02.610000000;13873.5\nBuy;3;2018-01-01D00:00:06.057000000;13874\n";
+        let schema = CsvSchema::new()
+            .with_columns("time", "px", "qty")
+            .with_side_column("side")
+            .with_delimiter(b';')
+            .with_timestamp_format(TimestampFormat::Iso8601);
+        let trades: Vec<Trade> = TradeCsvReader::with_schema(data.as_bytes(), schema)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            trades,
+            vec![
+                Trade {
+                    timestamp: 1_514_764_802_610,
+                    price: 13873.5,
+                    size: -5.0,
+                },
+                Trade {
+                    timestamp: 1_514_764_806_057,
+                    price: 13874.0,
+                    size: 3.0,
+                },
+            ]
+        );
+
+        // Without a header row
+        let data = "13873.5,1514764802610123,7\n";
+        let schema = CsvSchema::new()
+            .with_columns(1, 0, 2)
+            .with_headers(false)
+            .with_timestamp_resolution(TimestampResolution::Microsecond);
+        let mut reader = TradeCsvReader::with_schema(data.as_bytes(), schema).unwrap();
+        let trade = reader.next().unwrap().unwrap();
+        assert_eq!(trade.timestamp, 1_514_764_802_610);
+        assert_eq!(trade.price, 13873.5);
+
+        let schema = CsvSchema::new().with_columns("time", "price", "size");
+        assert!(TradeCsvReader::with_schema("timestamp,price,size\n".as_bytes(), schema).is_err());
+    }
+
+    #[test]
+    fn test_open_trade_file() {
+        let plain = TradeFileFormat::Okx.load("data/formats/okx.csv").unwrap();
+
+        for (filename, feature_enabled) in [
+            ("data/formats/okx.csv.gz", cfg!(feature = "gzip")),
+            ("data/formats/okx.csv.zst", cfg!(feature = "zstd")),
+        ] {
+            let trades = TradeFileFormat::Okx.load(filename);
+            if feature_enabled {
+                assert_eq!(trades.unwrap(), plain);
+            } else {
+                assert!(matches!(trades, Err(Error::UnsupportedCompression(_))));
+            }
+        }
+    }
+
+    #[test]
+    fn test_trade_csv_reader_aggregate() {
+        let trades = load_trades_from_csv("data/Bitmex_XBTUSD_1M.csv").unwrap();
+        let mut aggregator = GenericAggregator::<OhlcCandle, _, Trade>::new(TickRule::new(1000));
+        let expected = aggregate_all_trades(&trades, &mut aggregator);
+
+        let aggregator = GenericAggregator::<OhlcCandle, _, Trade>::new(TickRule::new(1000));
+        let candles: Vec<OhlcCandle> = TradeCsvReader::from_path("data/Bitmex_XBTUSD_1M.csv")
+            .unwrap()
+            .map(|trade| trade.unwrap())
+            .aggregate(aggregator)
+            .collect();
+        assert_eq!(candles.len(), expected.len());
+        for (c, e) in candles.iter().zip(expected.iter()) {
+            assert_eq!(c.open(), e.open());
+            assert_eq!(c.close(), e.close());
+        }
+    }
+
     #[test]
     fn test_candle_volume_from_time_period() {
         let total_volume = 100.0;
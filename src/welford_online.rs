@@ -44,6 +44,19 @@ impl WelfordOnline {
         self.mean += (val - old_mean) / self.count as f64;
         self.s += (val - old_mean) * (val - self.mean);
     }
+
+    // merge combines the statistics of two sets of values, using the parallel algorithm by Chan et al.
+    pub fn merge(&mut self, other: &Self) {
+        let count = self.count + other.count;
+        if count == 0 {
+            return;
+        }
+        let delta = other.mean - self.mean;
+        let weight = other.count as f64 / count as f64;
+        self.mean += delta * weight;
+        self.s += other.s + delta * delta * self.count as f64 * weight;
+        self.count = count;
+    }
 }
 
 #[cfg(test)]
@@ -63,4 +76,24 @@ mod tests {
         }
         assert_eq!(round(welford.std_dev(), 4), 0.5774);
     }
+
+    #[test]
+    fn welford_online_merge() {
+        let vals = [1.0, 2.0, 4.0, 7.0, 3.0, 9.0, 2.5];
+        let mut all = WelfordOnline::new();
+        for v in &vals {
+            all.add(*v);
+        }
+
+        for split in 0..vals.len() {
+            let mut a = WelfordOnline::new();
+            let mut b = WelfordOnline::new();
+            vals[..split].iter().for_each(|v| a.add(*v));
+            vals[split..].iter().for_each(|v| b.add(*v));
+            a.merge(&b);
+            assert_eq!(a.count, all.count);
+            assert_eq!(round(a.mean, 10), round(all.mean, 10));
+            assert_eq!(round(a.std_dev(), 10), round(all.std_dev(), 10));
+        }
+    }
 }
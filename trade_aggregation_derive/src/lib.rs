@@ -14,14 +14,28 @@
 //! - Trade
 //! - ModularCandle
 //! - CandleComponent
+//! - CandleComponentUpdate
+//!
+//! Adding the `#[candle(merge)]` attribute to the struct also implements 'ModularCandleMerge',
+//! which requires every 'CandleComponent' to implement 'CandleComponentMerge'.
+//! In that case, also make sure the following things are in scope:
+//! - ModularCandleMerge
+//! - CandleComponentMerge
+//!
+//! Adding the `#[candle(columns)]` attribute to the struct also implements 'CandleColumns',
+//! exposing the value of each 'CandleComponent' as a column named after the field,
+//! e.g.: to write candles to Parquet files.
+//! In that case, also make sure the following things are in scope:
+//! - CandleColumns
+//! - ColumnValue
 
 #![deny(missing_docs)]
 
 use proc_macro::TokenStream;
 use quote::{__private::Span, quote};
 use syn::{
-    self, AngleBracketedGenericArguments, Data, DataStruct, Fields, GenericArgument, Ident, Type,
-    TypePath,
+    self, AngleBracketedGenericArguments, Data, DataStruct, Fields, GenericArgument, Ident, Meta,
+    NestedMeta, Type, TypePath,
 };
 
 /// The 'Candle' macro takes a named struct,
@@ -30,7 +44,10 @@ use syn::{
 /// the 'ModularCandle' trait, which means it can then be used
 /// in the aggregation process.
 /// It also exposes getter functions for each 'CandleComponent' for convenience.
-#[proc_macro_derive(Candle)]
+/// With the `#[candle(merge)]` attribute, the 'ModularCandleMerge' trait is implemented as well.
+/// With the `#[candle(columns)]` attribute, the 'CandleColumns' trait is implemented as well.
+/// Both can be combined as `#[candle(merge, columns)]`.
+#[proc_macro_derive(Candle, attributes(candle))]
 pub fn candle_macro_derive(input: TokenStream) -> TokenStream {
     // Construct a representation of Rust code as a syntax tree
     // that we can manipulate
@@ -52,7 +69,7 @@ fn phantom_path_to_type(path: &syn::Path) -> Option<Ident> {
                 Some(GenericArgument::Type(Type::Path(TypePath {
                     path: syn::Path { segments: segs, .. },
                     ..
-                }))) => segs.first().and_then(|x| Some(x.ident.clone())),
+                }))) => segs.first().map(|x| x.ident.clone()),
                 _ => None,
             }
         }
@@ -60,8 +77,52 @@ fn phantom_path_to_type(path: &syn::Path) -> Option<Ident> {
     }
 }
 
+/// The options given in the `#[candle(...)]` attributes of the struct
+#[derive(Default)]
+struct CandleOptions {
+    merge: bool,
+    columns: bool,
+}
+
+/// Parse the `#[candle(...)]` attributes of the struct, e.g.: `#[candle(merge, columns)]`
+fn candle_options(ast: &syn::DeriveInput) -> syn::Result<CandleOptions> {
+    let mut options = CandleOptions::default();
+    for attr in ast.attrs.iter().filter(|attr| attr.path.is_ident("candle")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => {
+                return Err(syn::Error::new_spanned(
+                    meta,
+                    "expected #[candle(merge)] or #[candle(columns)]",
+                ))
+            }
+        };
+        for nested in &list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("merge") => {
+                    options.merge = true
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("columns") => {
+                    options.columns = true
+                }
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        nested,
+                        "unknown candle option, expected `merge` or `columns`",
+                    ))
+                }
+            }
+        }
+    }
+    Ok(options)
+}
+
 fn impl_candle_macro(ast: &syn::DeriveInput) -> TokenStream {
     let name = &ast.ident;
+    let options = match candle_options(ast) {
+        Ok(options) => options,
+        Err(err) => return err.to_compile_error().into(),
+    };
     let components = match &ast.data {
         Data::Struct(DataStruct {
             fields: Fields::Named(fields),
@@ -83,15 +144,13 @@ fn impl_candle_macro(ast: &syn::DeriveInput) -> TokenStream {
         } = c
         {
             if ident.clone().unwrap().to_string().as_str() == "input" {
-                input_type = phantom_path_to_type(&p);
+                input_type = phantom_path_to_type(p);
+            } else if let Some(type_) = phantom_path_to_type(p) {
+                value_idents.push(ident);
+                value_types.push(type_);
             } else {
-                if let Some(type_) = phantom_path_to_type(&p) {
-                    value_idents.push(ident);
-                    value_types.push(type_);
-                } else {
-                    value_idents.push(ident);
-                    value_types.push(default_output_type.clone());
-                }
+                value_idents.push(ident);
+                value_types.push(default_output_type.clone());
             }
         }
     }
@@ -99,11 +158,14 @@ fn impl_candle_macro(ast: &syn::DeriveInput) -> TokenStream {
     let fn_names0 = value_idents.clone();
     let fn_names1 = fn_names0.clone();
     let fn_names2 = fn_names1.clone();
+    let fn_names3 = fn_names2.clone();
+    let fn_names5 = fn_names3.clone();
     let input_name = input_type.expect("No PhantomData for input attribute type!");
 
     let gen = quote! {
         impl #name {
             #(
+                /// Get the value of this candle component.
                 pub fn #fn_names0(&self) -> #value_types {
                     self.#fn_names0.value()
                 }
@@ -122,8 +184,56 @@ fn impl_candle_macro(ast: &syn::DeriveInput) -> TokenStream {
                     self.#fn_names2.reset();
                 )*
             }
+
+            fn fill_gap(&mut self, timestamp: i64, price: f64) {
+                #(
+                    CandleComponentUpdate::<#input_name>::fill_gap(&mut self.#fn_names5, timestamp, price);
+                )*
+            }
+        }
+    };
+
+    let merge_gen = if options.merge {
+        quote! {
+            impl ModularCandleMerge<#input_name> for #name {
+                fn merge(&mut self, other: &Self) {
+                    #(
+                        self.#fn_names3.merge(&other.#fn_names3);
+                    )*
+                }
+            }
         }
+    } else {
+        quote! {}
     };
 
-    gen.into()
+    let columns_gen = if options.columns {
+        let column_names = value_idents
+            .iter()
+            .map(|ident| ident.as_ref().unwrap().to_string());
+        let column_indices = (0..value_idents.len()).map(syn::Index::from);
+        let fn_names4 = value_idents.clone();
+        quote! {
+            impl CandleColumns for #name {
+                fn column_names() -> &'static [&'static str] {
+                    &[#(#column_names),*]
+                }
+
+                fn write_columns(&self, push: &mut dyn FnMut(usize, ColumnValue)) {
+                    #(
+                        push(#column_indices, ColumnValue::from(self.#fn_names4()));
+                    )*
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    quote! {
+        #gen
+        #merge_gen
+        #columns_gen
+    }
+    .into()
 }